@@ -1,4 +1,11 @@
-use serde::{Deserialize, Serialize};
+use anyhow::Context;
+use serde::{
+    de::{
+        value::{MapAccessDeserializer, SeqAccessDeserializer},
+        MapAccess, SeqAccess, Visitor,
+    },
+    Deserialize, Deserializer, Serialize,
+};
 use std::{
     collections::HashMap,
     io::{Read, Write},
@@ -7,28 +14,105 @@ use std::{
 };
 use time::UtcOffset;
 
+/// fields missing from older configurations take their default value
 #[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Config {
     // the default address that will be displayed on launch
     pub default_address: String,
     // the maximum amount of entries displayed when synced with the host
     pub size: usize,
-    // how many entries the host should skip when syncing
-    pub skip: usize,
     // your local time offset, will default to UTC (meaning 0)
     pub local_offset: UtcOffset,
-    // hosts and their associated passwords
-    pub entries: HashMap<IpAddr, Vec<u8>>,
+    // hosts, their associated passwords, pinned identities and certificates
+    pub entries: HashMap<IpAddr, Server>,
+}
+
+/// a host known to the client
+#[derive(Serialize, Clone)]
+pub struct Server {
+    // the account to authenticate as
+    pub username: String,
+    #[serde(serialize_with = "laptev_protocol::password::serialize")]
+    pub password: Vec<u8>,
+    // the fingerprint of the host's identity, pinned on first use
+    pub identity: Option<String>,
+    // the fingerprint of the host's TLS certificate, connects over HTTPS only when set
+    pub certificate: Option<String>,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self {
+            username: default_username(),
+            password: Vec::new(),
+            identity: None,
+            certificate: None,
+        }
+    }
+}
+
+/// a host as the current format stores it
+#[derive(Deserialize)]
+struct ServerRepr {
+    #[serde(default = "default_username")]
+    username: String,
+    #[serde(deserialize_with = "laptev_protocol::password::deserialize")]
+    password: Vec<u8>,
+    #[serde(default)]
+    identity: Option<String>,
+    #[serde(default)]
+    certificate: Option<String>,
+}
+
+/// older configurations only stored the password of each host, as a list of numbers,
+/// telling the formats apart by their shape keeps the errors of the current one, e.g. a malformed password, as they are
+impl<'de> Deserialize<'de> for Server {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ServerVisitor;
+
+        impl<'de> Visitor<'de> for ServerVisitor {
+            type Value = Server;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str(
+                    "a host, or the list of numbers older versions stored its password as",
+                )
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Server, A::Error> {
+                let server = ServerRepr::deserialize(MapAccessDeserializer::new(map))?;
+                Ok(Server {
+                    username: server.username,
+                    password: server.password,
+                    identity: server.identity,
+                    certificate: server.certificate,
+                })
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Server, A::Error> {
+                Ok(Server {
+                    password: Vec::deserialize(SeqAccessDeserializer::new(seq))?,
+                    ..Default::default()
+                })
+            }
+        }
+
+        deserializer.deserialize_any(ServerVisitor)
+    }
+}
+
+fn default_username() -> String {
+    String::from("admin")
 }
 
 impl Default for Config {
     fn default() -> Self {
         let mut entries = HashMap::new();
-        entries.insert(IpAddr::from_str("127.0.0.1").unwrap(), Vec::new());
+        entries.insert(IpAddr::from_str("127.0.0.1").unwrap(), Server::default());
         Self {
             default_address: String::from("127.0.0.1:12675"),
             size: 25,
-            skip: 0,
             local_offset: UtcOffset::from_whole_seconds(0).unwrap(),
             entries,
         }
@@ -36,24 +120,31 @@ impl Default for Config {
 }
 
 impl Config {
-    pub fn new() -> Self {
+    /// loads laptev.config, a new one is only generated if there is none,
+    /// one that exists but fails to load is never overwritten, as that would lose every password in it
+    pub fn new() -> anyhow::Result<Self> {
         match Self::load() {
             Ok(config) => {
                 tracing::info!("configuration loaded from laptev.config");
-                config
+                Ok(config)
             }
-            Err(error) => {
-                tracing::warn!("failed to load configuration\n{}", error);
+            Err(error)
+                if error
+                    .downcast_ref::<std::io::Error>()
+                    .is_some_and(|error| error.kind() == std::io::ErrorKind::NotFound) =>
+            {
+                tracing::warn!("no configuration found, generating laptev.config");
                 let config = Self::default();
                 if let Err(error) = config.save() {
                     tracing::warn!("failed to save generated config\n{}", error);
                 }
-                config
+                Ok(config)
             }
+            Err(error) => Err(error),
         }
     }
 
-    fn save(&self) -> anyhow::Result<()> {
+    pub fn save(&self) -> anyhow::Result<()> {
         std::fs::OpenOptions::new()
             .create(true)
             .write(true)
@@ -72,6 +163,35 @@ impl Config {
             .open("laptev.config")?
             .read_to_end(&mut buffer)?;
 
-        Ok(serde_json::from_slice(&buffer)?)
+        // serde_json's errors point to the line and column at fault
+        let config: Self = serde_json::from_slice(&buffer).context("laptev.config is malformed")?;
+        config.validate().context("laptev.config is invalid")?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.size == 0 {
+            anyhow::bail!("size has to be at least 1");
+        }
+        for (address, server) in self.entries.iter() {
+            let fingerprints = [
+                ("identity", &server.identity),
+                ("certificate", &server.certificate),
+            ];
+            for (name, fingerprint) in fingerprints {
+                if let Some(fingerprint) = fingerprint {
+                    if fingerprint.len() != 64
+                        || !fingerprint.chars().all(|char| char.is_ascii_hexdigit())
+                    {
+                        anyhow::bail!(
+                            "the {} of {} is not a SHA-256 fingerprint (64 hexadecimal characters)",
+                            name,
+                            address
+                        );
+                    }
+                }
+            }
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,84 @@
+use laptev_protocol::identity::certificate_fingerprint;
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier},
+    Certificate, ServerName,
+};
+use std::{net::SocketAddr, sync::Arc, time::SystemTime};
+
+use crate::{
+    config::Server,
+    error::{self, Error},
+};
+
+/// how to reach a host: over plain HTTP, or over HTTPS when its certificate is pinned in configuration
+#[derive(Debug, Clone)]
+pub struct Connection {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl Connection {
+    pub fn new(socket_address: SocketAddr, server: &Server) -> error::Result<Self> {
+        match &server.certificate {
+            Some(pinned) => {
+                let tls = rustls::ClientConfig::builder()
+                    .with_safe_defaults()
+                    .with_custom_certificate_verifier(Arc::new(PinnedCertificate(
+                        pinned.to_lowercase(),
+                    )))
+                    .with_no_client_auth();
+                let client = reqwest::Client::builder()
+                    .use_preconfigured_tls(tls)
+                    .build()
+                    .map_err(|error| {
+                        tracing::error!("{}", error);
+                        Error::ServerNotResponding
+                    })?;
+                Ok(Self {
+                    base_url: format!("https://{}", socket_address),
+                    client,
+                })
+            }
+            None => Ok(Self {
+                base_url: format!("http://{}", socket_address),
+                client: reqwest::Client::new(),
+            }),
+        }
+    }
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+    pub fn client(&self) -> reqwest::Client {
+        self.client.clone()
+    }
+}
+
+/// accepts the host's self-signed certificate only if it matches the pinned fingerprint,
+/// the certificate's names and issuer are meaningless for a self-signed certificate
+struct PinnedCertificate(String);
+
+impl ServerCertVerifier for PinnedCertificate {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let fingerprint = certificate_fingerprint(&end_entity.0);
+        if fingerprint == self.0 {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            tracing::error!(
+                "WARNING: THE CERTIFICATE OF THE SERVER DOES NOT MATCH THE PINNED ONE\npinned : {}\nreceived : {}",
+                self.0,
+                fingerprint
+            );
+            Err(rustls::Error::General(String::from(
+                "certificate fingerprint mismatch",
+            )))
+        }
+    }
+}
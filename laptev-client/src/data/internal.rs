@@ -1,25 +1,66 @@
 use aes_gcm_siv::Aes256GcmSiv;
 use iced::{
     alignment,
-    widget::{button, row, text},
+    widget::{button, column, row, text},
     Element,
 };
-use std::sync::Arc;
+use laptev_protocol::{
+    action::{Action, ActionRequest},
+    list::EventMetadata,
+    role::{Permission, Role},
+    session::SessionId,
+    trash::TrashedEvent,
+    EncryptedMessage,
+};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 use time::UtcOffset;
 
 #[derive(Clone)]
-pub struct SharedCipher(Arc<Aes256GcmSiv>);
+pub struct SharedCipher {
+    cipher: Arc<Aes256GcmSiv>,
+    // the session id issued by the host, sent along every request
+    session: SessionId,
+    // what the account we authenticated as is allowed to do
+    role: Role,
+    // the sequence number of the next action, never reused within the session
+    sequence: Arc<AtomicU64>,
+}
 
 impl SharedCipher {
-    pub fn new(cipher: Aes256GcmSiv) -> Self {
-        Self(Arc::new(cipher))
+    pub fn new(cipher: Aes256GcmSiv, session: SessionId, role: Role) -> Self {
+        Self {
+            cipher: Arc::new(cipher),
+            session,
+            role,
+            sequence: Arc::new(AtomicU64::new(0)),
+        }
+    }
+    /// the body of a request changing something on the host, the session id alone is not enough for those
+    pub fn seal(&self, action: Action) -> Vec<u8> {
+        let request = ActionRequest {
+            sequence: self.sequence.fetch_add(1, Ordering::Relaxed),
+            action,
+        };
+        // unwrapping because this should never fail
+        EncryptedMessage::new(&bincode::serialize(&request).unwrap(), &self.cipher)
+            .unwrap()
+            .into_bytes()
+    }
+    pub fn session(&self) -> SessionId {
+        self.session
+    }
+    pub fn role(&self) -> Role {
+        self.role
     }
 }
 
 impl core::ops::Deref for SharedCipher {
     type Target = Arc<Aes256GcmSiv>;
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.cipher
     }
 }
 
@@ -61,20 +102,22 @@ impl FromIterator<Entry> for Entries {
     }
 }
 
-impl From<Vec<(u64, Vec<u8>)>> for Entries {
-    fn from(value: Vec<(u64, Vec<u8>)>) -> Self {
-        value.into_iter().map(Entry::from).collect::<Self>()
-    }
+/// the entries currently shown, along with the cursors to the pages around them
+#[derive(Default, Clone, Debug)]
+pub struct Page {
+    pub entries: Entries,
+    pub older: Option<u64>,
+    pub newer: Option<u64>,
 }
 
 impl Entries {
     pub fn clear(&mut self) {
         self.0.drain(..);
     }
-    pub fn to_widget(&self, local_offset: UtcOffset) -> Element<crate::Message> {
+    pub fn to_widget(&self, local_offset: UtcOffset, role: Role) -> Element<crate::Message> {
         let mut column: iced::widget::Column<crate::Message> = iced::widget::Column::new();
         for entry in self.iter() {
-            column = column.push(entry.to_widget(local_offset));
+            column = column.push(entry.to_widget(local_offset, role));
         }
         column.into()
     }
@@ -83,61 +126,175 @@ impl Entries {
 #[derive(Clone)]
 pub struct Entry {
     pub timestamp: u64,
-    pub thumbnail: Thumbnail,
+    // None if the host has no thumbnail for this event, or it could not be fetched
+    pub thumbnail: Option<Thumbnail>,
+    // the size of the clip in bytes and its duration in milliseconds, if there is a clip
+    pub clip_size: Option<u64>,
+    pub duration: Option<u64>,
+    // the clip is still being recorded, it can only be downloaded once a later sync shows it complete
+    pub recording: bool,
+    // the host keeps the event until it is unpinned, whatever its age
+    pub pinned: bool,
 }
 
-impl From<(u64, Vec<u8>)> for Entry {
-    fn from(value: (u64, Vec<u8>)) -> Self {
+impl Entry {
+    pub fn new(metadata: EventMetadata, thumbnail: Option<Thumbnail>) -> Self {
         Self {
-            timestamp: value.0,
-            thumbnail: Thumbnail::from(value.1),
+            timestamp: metadata.timestamp,
+            thumbnail,
+            clip_size: metadata.clip_size,
+            duration: metadata.duration,
+            recording: metadata.recording,
+            pinned: metadata.pinned,
         }
     }
-}
-
-impl Entry {
-    fn to_widget(&self, local_offset: time::UtcOffset) -> Element<crate::Message> {
-        row![
-            iced::widget::image(iced::widget::image::Handle::from_memory(
-                self.thumbnail.clone()
+    /// e.g. "12.3 s - 4.5 MB"
+    fn clip_description(&self) -> String {
+        if self.recording {
+            return String::from("recording...");
+        }
+        match (self.clip_size, self.duration) {
+            (None, _) => String::from("no clip"),
+            (Some(size), None) => format!("{:.1} MB", size as f64 / 1_000_000.0),
+            (Some(size), Some(duration)) => format!(
+                "{:.1} s - {:.1} MB",
+                duration as f64 / 1000.0,
+                size as f64 / 1_000_000.0
+            ),
+        }
+    }
+    fn to_widget(&self, local_offset: time::UtcOffset, role: Role) -> Element<crate::Message> {
+        let thumbnail: Element<crate::Message> = match &self.thumbnail {
+            Some(thumbnail) => iced::widget::image(iced::widget::image::Handle::from_memory(
+                thumbnail.clone(),
             ))
             .width(512)
-            .height(288),
-            if let Ok(time) = time::OffsetDateTime::from_unix_timestamp(self.timestamp as i64) {
-                let t = time.to_offset(local_offset);
+            .height(288)
+            .into(),
+            None => text("no thumbnail")
+                .width(512)
+                .height(288)
+                .vertical_alignment(alignment::Vertical::Center)
+                .horizontal_alignment(alignment::Horizontal::Center)
+                .into(),
+        };
+        let time = text(format_timestamp(self.timestamp, local_offset));
+        let mut description = column![time, text(self.clip_description()).size(14)]
+            .spacing(5)
+            .align_items(alignment::Alignment::Center);
+        if self.pinned {
+            description = description.push(text("pinned").size(14));
+        }
+        let mut row = row![thumbnail, description];
+        // only shows the buttons our role allows us to use
+        if role.allows(Permission::Download) && self.clip_size.is_some() {
+            row = row.push(
+                button(text("download"))
+                    .on_press(crate::Message::Download(self.timestamp))
+                    .padding(10)
+                    .style(iced::theme::Button::Positive),
+            );
+        }
+        if role.allows(Permission::Delete) {
+            let pin = if self.pinned { "unpin" } else { "pin" };
+            row = row.push(
+                button(text(pin))
+                    .on_press(crate::Message::Pin(self.timestamp, !self.pinned))
+                    .padding(10),
+            );
+            row = row.push(
+                button(text("delete"))
+                    .on_press(crate::Message::Delete(self.timestamp))
+                    .padding(10)
+                    .style(iced::theme::Button::Destructive),
+            );
+        }
+        row.align_items(alignment::Alignment::Center)
+            .padding(10)
+            .spacing(20)
+            .into()
+    }
+}
+
+/// e.g. "24/12/2023 - 18:03:27", the timestamp itself if it is out of range
+fn format_timestamp(timestamp: u64, local_offset: UtcOffset) -> String {
+    match time::OffsetDateTime::from_unix_timestamp(timestamp as i64) {
+        Ok(time) => {
+            let t = time.to_offset(local_offset);
+            format!(
+                "{:0>2}/{:0>2}/{} - {:0>2}:{:0>2}:{:0>2}",
+                t.day(),
+                t.month() as u8,
+                t.year(),
+                t.hour(),
+                t.minute(),
+                t.second()
+            )
+        }
+        Err(..) => timestamp.to_string(),
+    }
+}
+
+/// the deleted events the host can still restore, most recently deleted first
+#[derive(Default, Clone)]
+pub struct Trash(pub Vec<TrashedEvent>);
+
+impl std::fmt::Debug for Trash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Trash")
+    }
+}
+
+impl Trash {
+    pub fn remove(&mut self, timestamp: u64) {
+        self.0.retain(|event| event.timestamp != timestamp);
+    }
+    pub fn to_widget(&self, local_offset: UtcOffset) -> Element<crate::Message> {
+        let mut list: iced::widget::Column<crate::Message> = iced::widget::Column::new()
+            .spacing(10)
+            .align_items(alignment::Alignment::Center);
+        if self.0.is_empty() {
+            list = list.push(text("the trash is empty"));
+        }
+        for event in self.0.iter() {
+            let size = event.thumbnail_size.unwrap_or(0) + event.clip_size.unwrap_or(0);
+            let description = column![
+                text(format_timestamp(event.timestamp, local_offset)),
                 text(format!(
-                    "{:0>2}/{:0>2}/{} - {:0>2}:{:0>2}:{:0>2}",
-                    t.day(),
-                    t.month() as u8,
-                    t.year(),
-                    t.hour(),
-                    t.minute(),
-                    t.second()
+                    "{:.1} MB - purged on {}",
+                    size as f64 / 1_000_000.0,
+                    format_timestamp(event.purge_at, local_offset)
                 ))
-            } else {
-                text(self.timestamp)
-            }
-            .vertical_alignment(alignment::Vertical::Center)
-            .horizontal_alignment(alignment::Horizontal::Center),
-            button(text("download"))
-                .on_press(crate::Message::Download(self.timestamp))
-                .padding(10)
-                .style(iced::theme::Button::Positive),
-            button(text("delete"))
-                .on_press(crate::Message::Delete(self.timestamp))
+                .size(14),
+            ]
+            .spacing(5)
+            .align_items(alignment::Alignment::Center);
+            list = list.push(
+                row![
+                    description,
+                    button(text("restore"))
+                        .on_press(crate::Message::Restore(event.timestamp))
+                        .padding(10)
+                        .style(iced::theme::Button::Positive),
+                ]
+                .align_items(alignment::Alignment::Center)
                 .padding(10)
-                .style(iced::theme::Button::Destructive),
-        ]
-        .align_items(alignment::Alignment::Center)
-        .padding(10)
-        .spacing(20)
-        .into()
+                .spacing(20),
+            );
+        }
+        list.into()
     }
 }
 
 #[derive(Clone)]
 pub struct Thumbnail(pub Arc<Vec<u8>>);
 
+impl std::fmt::Debug for Thumbnail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Thumbnail")
+    }
+}
+
 impl std::ops::Deref for Thumbnail {
     type Target = Vec<u8>;
     fn deref(&self) -> &Self::Target {
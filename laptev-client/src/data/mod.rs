@@ -1,2 +1 @@
-pub mod external;
 pub mod internal;
@@ -15,6 +15,8 @@ pub enum HandshakeFailedReason {
     UknownServer,
     KeyExchangeFailed,
     AuthenticationFailed,
+    IdentityMismatch,
+    Throttled,
 }
 
 impl fmt::Display for Error {
@@ -40,6 +42,10 @@ impl std::error::Error for Error {
                     HFR::AuthenticationFailed => {
                         "could not authenticate, password probably incorrect"
                     }
+                    HFR::IdentityMismatch => {
+                        "the server's identity does not match the one pinned in configuration"
+                    }
+                    HFR::Throttled => "too many failed attempts, wait before trying again",
                 }
             }
             Self::InvalidSocketAddr => "not a valid socket addr",
@@ -1,10 +1,30 @@
-use aes_gcm_siv::{Aes256GcmSiv, KeyInit};
-use rand::{rngs::StdRng, SeedableRng};
+use laptev_protocol::{
+    action::Action,
+    delete::DeleteResponse,
+    download::DownloadRequest,
+    endpoint,
+    handshake::{
+        AuthenticationRequest, AuthenticationResponse, HandshakeStep, KeyExchangeRequest,
+        KeyExchangeResponse,
+    },
+    identity::{fingerprint, verify_key_exchange},
+    list::ListResponse,
+    notification::Notification,
+    pake::{Side, Spake2},
+    role::{Permission, Role},
+    session::SESSION_HEADER,
+    stream::{StreamDecryptor, CHUNK_SIZE},
+    sync::SyncRequest,
+    trash::TrashResponse,
+    EncryptedMessage,
+};
 use reqwest::{Method, StatusCode, Url};
-use std::{fmt::Debug, net::SocketAddr, path::PathBuf, str::FromStr};
-use tokio::io::AsyncWriteExt;
+use std::{
+    collections::HashMap, fmt::Debug, io::SeekFrom, net::SocketAddr, path::PathBuf,
+    str::FromStr,
+};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
 use utils::invisible_rule;
-use x25519_dalek::{EphemeralSecret, PublicKey};
 
 use iced::{
     alignment, color,
@@ -17,17 +37,19 @@ use iced::{
 
 mod config;
 use config::Config;
+mod connection;
+use connection::Connection;
 mod data;
-use data::{
-    external::EncryptedMessage,
-    internal::{Entries, SharedCipher},
-};
+use data::internal::{Entries, Entry, Page, SharedCipher, Thumbnail, Trash};
 mod error;
 use error::Error;
+mod notifications;
 mod utils;
 
 const ICON_CLEAR: &'static [u8] = include_bytes!("../res/icon-clear.png");
 const ICON_CHILLY: &'static [u8] = include_bytes!("../res/icon-chilly.png");
+// how many times a download is attempted before giving up
+const DOWNLOAD_ATTEMPTS: usize = 5;
 
 #[tokio::main]
 async fn main() -> iced::Result {
@@ -36,7 +58,17 @@ async fn main() -> iced::Result {
         .compact()
         .init();
 
-    let settings: iced::Settings<()> = iced::Settings {
+    // a configuration that fails to load is never overwritten, the user has to fix it first
+    let config = match Config::new() {
+        Ok(config) => config,
+        Err(error) => {
+            tracing::error!("{:#}", error);
+            std::process::exit(1);
+        }
+    };
+
+    let settings: iced::Settings<Config> = iced::Settings {
+        flags: config,
         window: iced::window::Settings {
             size: (300, 400),
             resizable: true,
@@ -64,8 +96,17 @@ struct Laptev {
     socket_address: String,
     // Option<> becaue we don't always have a cipher
     cipher: Option<SharedCipher>,
-    // represents the entries shown when our app is synced, u64: timestamp, Vec<u8> thumbnail
-    entries: Entries,
+    // how to reach the host we are synced with, over HTTP or HTTPS
+    connection: Option<Connection>,
+    // represents the entries shown when our app is synced, u64: timestamp, Vec<u8> thumbnail,
+    // along with the cursors to the older and newer pages
+    page: Page,
+    // the thumbnails already fetched, by timestamp, so that paging back and forth does not fetch them again
+    thumbnails: HashMap<u64, Thumbnail>,
+    // the deleted events shown in the trash view, as of when it was opened
+    trash: Trash,
+    // the last entry we deleted, offered to be restored until it is
+    undo: Option<u64>,
 }
 
 impl Laptev {
@@ -78,79 +119,120 @@ impl Laptev {
     fn clear(&mut self) {
         self.mode = Mode::Initial;
         self.cipher = None;
-        self.entries.drain(..);
+        self.connection = None;
+        self.page = Page::default();
+        self.thumbnails.clear();
+        self.trash = Trash::default();
+        self.undo = None;
     }
     async fn authenticate(
         socket_address: SocketAddr,
         config: Config,
-    ) -> error::Result<SharedCipher> {
+    ) -> error::Result<(SharedCipher, Connection, String)> {
         use error::HandshakeFailedReason as HFR;
-        let base_url: String = format!("http://{}/", socket_address);
-
-        // step 1, checking if the server is online
-        let url: String = format!("{}status", base_url);
-        let _ = reqwest::get(&url).await.map_err(|error| {
-            tracing::error!("{}", error);
-            Error::HandshakeFailed(HFR::ServerNotResponding)
-        })?;
 
-        // step 2, checking we have the password to the server
-        let password = config
+        // step 1, checking we have the password to the server, and whether it is served over HTTPS
+        let server = config
             .entries
             .get(&socket_address.ip())
             .ok_or(Error::HandshakeFailed(HFR::UknownServer))?;
+        let connection = Connection::new(socket_address, server)?;
+
+        // step 2, checking if the server is online
+        let url: String = connection.url(endpoint::STATUS);
+        let _ = connection.client().get(&url).send().await.map_err(|error| {
+            tracing::error!("{}", error);
+            Error::HandshakeFailed(HFR::ServerNotResponding)
+        })?;
 
         // step 3, key exchange
-        let url: String = format!("{}handshake/0", base_url);
-        let client_private_key = EphemeralSecret::random_from_rng(StdRng::from_entropy());
-        let client_public_key = PublicKey::from(&client_private_key);
+        let url: String = connection.url(&endpoint::handshake(HandshakeStep::KeyExchange));
+        let spake2 = Spake2::start(Side::Client, &server.username, &server.password);
+        let request_body = KeyExchangeRequest {
+            username: server.username.clone(),
+            message: spake2.message(),
+        };
 
-        let client = reqwest::Client::new();
+        let client = connection.client();
         let request = reqwest::Request::new(Method::PUT, Url::from_str(url.as_str()).unwrap());
 
         let response = reqwest::RequestBuilder::from_parts(client, request)
-            .body(client_public_key.as_bytes().to_vec())
+            .body(bincode::serialize(&request_body).unwrap())
             .send()
             .await
             .map_err(|error| {
                 tracing::error!("{}", error);
                 Error::HandshakeFailed(HFR::KeyExchangeFailed)
             })?;
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            return Err(Error::HandshakeFailed(HFR::Throttled));
+        }
 
         let body = response.bytes().await.map_err(|error| {
             tracing::error!("{}", error);
             Error::HandshakeFailed(HFR::KeyExchangeFailed)
         })?;
 
-        let mut server_public_key: [u8; 32] = [0; 32];
-        if body.len() != 32 {
-            tracing::error!("did not receive a 32-byte key from the server");
-            return Err(Error::HandshakeFailed(HFR::KeyExchangeFailed));
+        let key_exchange_response: KeyExchangeResponse =
+            bincode::deserialize(&body).map_err(|error| {
+                tracing::error!("did not receive a valid key exchange response\n{}", error);
+                Error::HandshakeFailed(HFR::KeyExchangeFailed)
+            })?;
+        let session = key_exchange_response.session;
+
+        // step 4, checking the server's identity, pinned on first use
+        verify_key_exchange(
+            &key_exchange_response.identity,
+            &request_body.message,
+            &key_exchange_response.message,
+            &key_exchange_response.signature,
+        )
+        .map_err(|error| {
+            tracing::error!("the server's signature is invalid\n{}", error);
+            Error::HandshakeFailed(HFR::KeyExchangeFailed)
+        })?;
+        let identity = fingerprint(&key_exchange_response.identity);
+        match &server.identity {
+            Some(pinned) if *pinned != identity => {
+                tracing::error!(
+                    "WARNING: THE IDENTITY OF {} HAS CHANGED\npinned : {}\nreceived : {}\nsomeone may be impersonating the server, if you regenerated laptev.identity on purpose remove the pinned identity from laptev.config",
+                    socket_address.ip(),
+                    pinned,
+                    identity
+                );
+                return Err(Error::HandshakeFailed(HFR::IdentityMismatch));
+            }
+            Some(..) => (),
+            None => tracing::warn!(
+                "trusting the identity of {} on first use : {}",
+                socket_address.ip(),
+                identity
+            ),
         }
-        for (idx, byte) in body.into_iter().enumerate() {
-            server_public_key[idx] = byte;
+
+        // step 5, deriving the keys and checking that the server knows the password
+        let keys = spake2
+            .finish(&key_exchange_response.message)
+            .map_err(|error| {
+                tracing::error!("{}", error);
+                Error::HandshakeFailed(HFR::KeyExchangeFailed)
+            })?;
+        if keys.host_confirmation() != key_exchange_response.confirmation {
+            tracing::error!("the server could not prove it knows the password");
+            return Err(Error::HandshakeFailed(HFR::AuthenticationFailed));
         }
-        let server_public_key = PublicKey::from(server_public_key);
-
-        // step 4, building the cipher
-        // we can unwrap since at this point it is guaranteed that our key will be 32-bytes
-        let symetric_key = client_private_key
-            .diffie_hellman(&server_public_key)
-            .as_bytes()
-            .to_owned();
-        let cipher = Aes256GcmSiv::new_from_slice(&symetric_key).unwrap();
-
-        // step 5, authentication
-        let url: String = format!("{}handshake/1", base_url);
-        let client = reqwest::Client::new();
+
+        // step 6, authentication
+        let url: String = connection.url(&endpoint::handshake(HandshakeStep::Authentication));
+        let request_body = AuthenticationRequest {
+            confirmation: keys.client_confirmation(),
+        };
+        let client = connection.client();
         let request = reqwest::Request::new(Method::PUT, Url::from_str(url.as_str()).unwrap());
 
         let response = reqwest::RequestBuilder::from_parts(client, request)
-            .body(
-                EncryptedMessage::new(password.as_ref(), &cipher)
-                    .unwrap()
-                    .into_bytes(),
-            )
+            .header(SESSION_HEADER, session.to_string())
+            .body(bincode::serialize(&request_body).unwrap())
             .send()
             .await
             .map_err(|error| {
@@ -158,144 +240,433 @@ impl Laptev {
                 Error::HandshakeFailed(HFR::AuthenticationFailed)
             })?;
 
-        if response.status() == StatusCode::OK {
-            Ok(SharedCipher::new(cipher))
-        } else {
-            Err(Error::HandshakeFailed(HFR::AuthenticationFailed))
+        if response.status() != StatusCode::OK {
+            return Err(Error::HandshakeFailed(HFR::AuthenticationFailed));
+        }
+
+        // step 7, learning what our account is allowed to do
+        let cipher = keys.cipher();
+        let authentication_response: AuthenticationResponse = response
+            .bytes()
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|body| EncryptedMessage::try_from_bytes(&body))
+            .and_then(|message| message.try_decrypt(&cipher))
+            .and_then(|data| Ok(bincode::deserialize(&data)?))
+            .map_err(|error| {
+                tracing::error!("{}", error);
+                Error::HandshakeFailed(HFR::AuthenticationFailed)
+            })?;
+
+        Ok((
+            SharedCipher::new(cipher, session, authentication_response.role),
+            connection,
+            identity,
+        ))
+    }
+    /// applies a change pushed by the host to the page shown, without syncing again
+    fn notify(&mut self, notification: Notification) -> Command<Message> {
+        let metadata = match notification {
+            Notification::New(metadata) | Notification::Completed(metadata) => metadata,
+            Notification::Deleted(id) => {
+                self.page.entries.retain(|entry| entry.timestamp != id);
+                self.thumbnails.remove(&id);
+                return Command::none();
+            }
+            // only concerns the entries already shown
+            Notification::Updated(metadata) => {
+                if let Some(entry) = self
+                    .page
+                    .entries
+                    .iter_mut()
+                    .find(|entry| entry.timestamp == metadata.timestamp)
+                {
+                    *entry = Entry::new(metadata, entry.thumbnail.clone());
+                }
+                return Command::none();
+            }
+        };
+        let id = metadata.timestamp;
+        let thumbnail = self.thumbnails.get(&id).cloned();
+        let fetch_thumbnail = thumbnail.is_none() && metadata.thumbnail_size.is_some();
+
+        // entries are sorted from the most recent to the oldest
+        let entries = &mut self.page.entries;
+        match entries.iter().position(|entry| entry.timestamp <= id) {
+            Some(index) if entries[index].timestamp == id => {
+                entries[index] = Entry::new(metadata, thumbnail)
+            }
+            // only the most recent page grows, the others are reached through their cursors
+            _ if self.page.newer.is_some() => return Command::none(),
+            Some(index) => entries.insert(index, Entry::new(metadata, thumbnail)),
+            None if entries.len() < self.config.size => {
+                entries.push(Entry::new(metadata, thumbnail))
+            }
+            None => return Command::none(),
+        }
+        if entries.len() > self.config.size {
+            entries.pop();
+            self.page.older = entries.last().map(|entry| entry.timestamp);
+        }
+
+        match (fetch_thumbnail, self.connection.clone(), self.cipher.clone()) {
+            (true, Some(connection), Some(shared_cipher)) => Command::perform(
+                async move { (id, Self::thumbnail(id, &connection, &shared_cipher).await) },
+                Message::ThumbnailOutput,
+            ),
+            _ => Command::none(),
         }
     }
+    /// shows the loading screen while the requested page is fetched
+    fn sync_page(&mut self, parameters: SyncRequest) -> Command<Message> {
+        self.mode = Mode::Syncing;
+        let connection = self.connection.clone().unwrap();
+        let shared_cipher = self.cipher.as_ref().unwrap().clone();
+        let thumbnails = self.thumbnails.clone();
+        Command::batch([
+            iced::window::resize(Size::new(300, 400)),
+            Command::perform(
+                async move { Self::sync(connection, shared_cipher, parameters, thumbnails).await },
+                Message::SyncOutput,
+            ),
+        ])
+    }
+    /// lists the events of the requested page, then fetches the thumbnails that are not cached yet
     async fn sync(
-        socket_address: SocketAddr,
+        connection: Connection,
         cipher: SharedCipher,
-        config: Config,
-    ) -> error::Result<Entries> {
-        let url: String = format!(
-            "http://{}/synchronize?size={}&skip={}",
-            socket_address, config.size, config.skip
-        );
-        let response = reqwest::get(Url::from_str(&url).unwrap())
+        parameters: SyncRequest,
+        mut thumbnails: HashMap<u64, Thumbnail>,
+    ) -> error::Result<Page> {
+        let url: String = connection.url(endpoint::LIST);
+        let response = connection
+            .client()
+            .get(Url::from_str(&url).unwrap())
+            .header(SESSION_HEADER, cipher.session().to_string())
+            .query(&parameters)
+            .send()
             .await
             .map_err(|error| {
                 tracing::warn!("{}", error);
                 Error::ServerNotResponding
             })?;
-        if response.status() == StatusCode::FORBIDDEN {
-            return Err(Error::Forbidden);
-        }
-
-        let response = EncryptedMessage::try_from_bytes(&response.bytes().await.unwrap()).unwrap();
-
-        Ok(Entries::from(
-            bincode::deserialize::<Vec<(u64, Vec<u8>)>>(&response.try_decrypt(&cipher).unwrap())
-                .unwrap(),
-        ))
-    }
-    async fn download(id: u64, socket_address: SocketAddr, cipher: SharedCipher) {
-        let url: String = format!("http://{}/download/{}", socket_address, id);
-        let response = reqwest::get(Url::from_str(&url).unwrap()).await;
-        if let Err(error) = response {
-            tracing::warn!("{}", error);
-            return;
+        match response.status() {
+            StatusCode::OK => (),
+            StatusCode::FORBIDDEN => return Err(Error::Forbidden),
+            status => {
+                tracing::warn!(
+                    "did not receive a response with an OK status, got status : {}",
+                    status
+                );
+                return Err(Error::ServerNotResponding);
+            }
         }
-        let response = response.unwrap();
-        if response.status() != StatusCode::OK {
-            tracing::warn!(
-                "did not receive a response with an OK status, got status : {}",
-                response.status()
-            );
-            return;
+        let result: anyhow::Result<ListResponse> = async {
+            let data =
+                EncryptedMessage::try_from_bytes(&response.bytes().await?)?.try_decrypt(&cipher)?;
+            Ok(bincode::deserialize(&data)?)
         }
-
-        let encrypted_data = response.bytes().await;
-        if let Err(error) = encrypted_data {
+        .await;
+        let list = result.map_err(|error| {
             tracing::warn!("{}", error);
-            return;
+            Error::ServerNotResponding
+        })?;
+
+        let mut entries = Entries::default();
+        for event in list.events.into_iter() {
+            let thumbnail = match (thumbnails.remove(&event.timestamp), event.thumbnail_size) {
+                (Some(thumbnail), _) => Some(thumbnail),
+                (None, Some(..)) => Self::thumbnail(event.timestamp, &connection, &cipher).await,
+                (None, None) => None,
+            };
+            entries.push(Entry::new(event, thumbnail));
         }
-        let encrypted_data = EncryptedMessage::try_from_bytes(&encrypted_data.unwrap());
-        if let Err(error) = encrypted_data {
-            tracing::warn!("{}", error);
-            return;
+        Ok(Page {
+            entries,
+            older: list.older,
+            newer: list.newer,
+        })
+    }
+    /// a missing thumbnail is not worth failing the whole page over, the entry is shown without it
+    async fn thumbnail(
+        id: u64,
+        connection: &Connection,
+        cipher: &SharedCipher,
+    ) -> Option<Thumbnail> {
+        let url: String = connection.url(&endpoint::thumbnail(id));
+        let result: anyhow::Result<Vec<u8>> = async {
+            let response = connection
+                .client()
+                .get(Url::from_str(&url)?)
+                .header(SESSION_HEADER, cipher.session().to_string())
+                .send()
+                .await?
+                .error_for_status()?;
+            EncryptedMessage::try_from_bytes(&response.bytes().await?)?.try_decrypt(cipher)
         }
-        let decrypted_data = encrypted_data.unwrap().try_decrypt(&cipher);
-        if let Err(error) = decrypted_data {
-            tracing::warn!("{}", error);
-            return;
+        .await;
+        match result {
+            Ok(data) => Some(Thumbnail::from(data)),
+            Err(error) => {
+                tracing::warn!("could not fetch the thumbnail of {}\n{}", id, error);
+                None
+            }
         }
-        
-        let filepath: PathBuf = match PathBuf::from("./downloads").is_dir() {
-            true => PathBuf::from(format!("./downloads/{}.mp4", id)),
+    }
+    async fn download(id: u64, connection: Connection, cipher: SharedCipher) {
+        let directory: PathBuf = match PathBuf::from("./downloads").is_dir() {
+            true => PathBuf::from("./downloads"),
             false => {
                 match tokio::fs::create_dir("./downloads").await {
-                    Ok(..) => PathBuf::from(format!("./downloads/{}.mp4", id)),
+                    Ok(..) => PathBuf::from("./downloads"),
                     Err(error) => {
                         tracing::warn!("could not create ./downloads\n{}", error);
-                        PathBuf::from(format!("./{}.mp4", id))
+                        PathBuf::from(".")
                     },
                 }
             }
         };
+        let filepath = directory.join(format!("{}.mp4", id));
+        let partpath = directory.join(format!("{}.mp4.part", id));
 
-        let file = tokio::fs::OpenOptions::new()
+        // every attempt resumes where the previous one stopped
+        for attempt in 1..=DOWNLOAD_ATTEMPTS {
+            match Self::try_download(id, &connection, &cipher, &partpath).await {
+                Ok(()) => {
+                    if let Err(error) = tokio::fs::rename(&partpath, &filepath).await {
+                        tracing::warn!("{}", error);
+                    }
+                    return;
+                }
+                Err(error) => {
+                    tracing::warn!(
+                        "download attempt {} out of {} failed\n{}",
+                        attempt,
+                        DOWNLOAD_ATTEMPTS,
+                        error
+                    );
+                    // no point in retrying once the session is gone
+                    if error.downcast_ref::<Error>().is_some() {
+                        return;
+                    }
+                    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+                }
+            }
+        }
+    }
+    /// downloads the clip into its .part file, decrypting and writing it one chunk at a time as it is received,
+    /// the .part file only ever holds whole authenticated chunks, which is where the download resumes from
+    async fn try_download(
+        id: u64,
+        connection: &Connection,
+        cipher: &SharedCipher,
+        partpath: &std::path::Path,
+    ) -> anyhow::Result<()> {
+        let mut file = tokio::fs::OpenOptions::new()
             .create(true)
             .write(true)
-            .truncate(true)
-            .open(filepath)
-            .await;
-        if let Err(error) = file {
-            tracing::warn!("{}", error);
-            return;
+            .open(partpath)
+            .await?;
+        let length = file.metadata().await?.len();
+        let request = DownloadRequest {
+            offset: length - length % CHUNK_SIZE as u64,
+        };
+        let first_chunk = request
+            .first_chunk()
+            .ok_or(anyhow::anyhow!("the partial download is too large"))?;
+        file.set_len(request.offset).await?;
+        file.seek(SeekFrom::Start(request.offset)).await?;
+
+        let url: String = connection.url(&endpoint::download(id));
+        let mut response = connection
+            .client()
+            .get(Url::from_str(&url).unwrap())
+            .header(SESSION_HEADER, cipher.session().to_string())
+            .query(&request)
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::OK | StatusCode::PARTIAL_CONTENT => (),
+            StatusCode::FORBIDDEN => return Err(Error::Forbidden.into()),
+            StatusCode::RANGE_NOT_SATISFIABLE => {
+                // the .part file does not belong to this clip, starts over
+                file.set_len(0).await?;
+                anyhow::bail!("the host refused to resume from {}", request.offset);
+            }
+            status => anyhow::bail!(
+                "did not receive a response with an OK status, got status : {}",
+                status
+            ),
         }
 
-        let mut file = file.unwrap();
-        if let Err(error) = file.write_all(&decrypted_data.unwrap()).await {
-            tracing::warn!("{}", error);
+        let mut decryptor = StreamDecryptor::starting_at(id, first_chunk);
+        while let Some(bytes) = response.chunk().await? {
+            file.write_all(&decryptor.push(&bytes, cipher)?).await?;
         }
+        file.write_all(&decryptor.finish(cipher)?).await?;
+        file.flush().await?;
+        Ok(())
     }
-    async fn delete(id: u64, socket_address: SocketAddr) {
-        let url: String = format!("http://{}/delete/{}", socket_address, id);
-        let client = reqwest::Client::new();
+    /// returns what became of the files of the event, None if it was not deleted
+    async fn delete(
+        id: u64,
+        connection: Connection,
+        cipher: SharedCipher,
+    ) -> Option<DeleteResponse> {
+        let url: String = connection.url(&endpoint::delete(id));
+        let client = connection.client();
         let request = reqwest::Request::new(Method::DELETE, Url::from_str(url.as_str()).unwrap());
 
         let response = reqwest::RequestBuilder::from_parts(client, request)
+            .header(SESSION_HEADER, cipher.session().to_string())
+            .body(cipher.seal(Action::Delete { id }))
             .send()
             .await;
         if let Err(error) = response {
             tracing::warn!("{}", error);
-            return;
+            return None;
         }
         let response = response.unwrap();
 
-        if response.status() != StatusCode::OK {
-            tracing::warn!(
+        match response.status() {
+            StatusCode::OK => (),
+            StatusCode::CONFLICT => {
+                tracing::warn!(
+                    "{} is still being recorded, delete it once it is complete",
+                    id
+                );
+                return None;
+            }
+            status => {
+                tracing::warn!(
+                    "did not receive a response with an OK status, got status : {}",
+                    status
+                );
+                return None;
+            }
+        }
+        let result: anyhow::Result<DeleteResponse> = async {
+            let data =
+                EncryptedMessage::try_from_bytes(&response.bytes().await?)?.try_decrypt(&cipher)?;
+            Ok(bincode::deserialize(&data)?)
+        }
+        .await;
+        match result {
+            Ok(deleted) => Some(deleted),
+            Err(error) => {
+                tracing::warn!("{}", error);
+                None
+            }
+        }
+    }
+    async fn trash(connection: Connection, cipher: SharedCipher) -> error::Result<Trash> {
+        let url: String = connection.url(endpoint::TRASH);
+        let response = connection
+            .client()
+            .get(Url::from_str(&url).unwrap())
+            .header(SESSION_HEADER, cipher.session().to_string())
+            .send()
+            .await
+            .map_err(|error| {
+                tracing::warn!("{}", error);
+                Error::ServerNotResponding
+            })?;
+        match response.status() {
+            StatusCode::OK => (),
+            StatusCode::FORBIDDEN => return Err(Error::Forbidden),
+            status => {
+                tracing::warn!(
+                    "did not receive a response with an OK status, got status : {}",
+                    status
+                );
+                return Err(Error::ServerNotResponding);
+            }
+        }
+        let result: anyhow::Result<TrashResponse> = async {
+            let data =
+                EncryptedMessage::try_from_bytes(&response.bytes().await?)?.try_decrypt(&cipher)?;
+            Ok(bincode::deserialize(&data)?)
+        }
+        .await;
+        result.map(Trash).map_err(|error| {
+            tracing::warn!("{}", error);
+            Error::ServerNotResponding
+        })
+    }
+    /// returns whether the event was restored, the page is updated once the host notifies us of it
+    async fn restore(id: u64, connection: Connection, cipher: SharedCipher) -> bool {
+        let url: String = connection.url(&endpoint::restore(id));
+        let client = connection.client();
+        let request = reqwest::Request::new(Method::POST, Url::from_str(url.as_str()).unwrap());
+
+        let response = reqwest::RequestBuilder::from_parts(client, request)
+            .header(SESSION_HEADER, cipher.session().to_string())
+            .body(cipher.seal(Action::Restore { id }))
+            .send()
+            .await;
+        match response {
+            Ok(response) if response.status() != StatusCode::OK => {
+                tracing::warn!(
+                    "did not receive a response with an OK status, got status : {}",
+                    response.status()
+                );
+                false
+            }
+            Ok(_) => true,
+            Err(error) => {
+                tracing::warn!("{}", error);
+                false
+            }
+        }
+    }
+    /// the page is updated once the host notifies us of the change
+    async fn pin(id: u64, pinned: bool, connection: Connection, cipher: SharedCipher) {
+        let url: String = connection.url(&endpoint::pin(id));
+        let method = if pinned { Method::PUT } else { Method::DELETE };
+        let client = connection.client();
+        let request = reqwest::Request::new(method, Url::from_str(url.as_str()).unwrap());
+
+        let response = reqwest::RequestBuilder::from_parts(client, request)
+            .header(SESSION_HEADER, cipher.session().to_string())
+            .body(cipher.seal(Action::Pin { id, pinned }))
+            .send()
+            .await;
+        match response {
+            Ok(response) if response.status() != StatusCode::OK => tracing::warn!(
                 "did not receive a response with an OK status, got status : {}",
                 response.status()
-            );
+            ),
+            Ok(_) => (),
+            Err(error) => tracing::warn!("{}", error),
         }
     }
 }
 
-impl Default for Laptev {
-    fn default() -> Self {
-        let config = Config::new();
+impl From<Config> for Laptev {
+    fn from(config: Config) -> Self {
         let default_address = config.default_address.clone();
         Self {
             config,
             mode: Mode::Initial,
             socket_address: default_address,
             cipher: None,
-            entries: Entries::default(),
+            connection: None,
+            page: Page::default(),
+            thumbnails: HashMap::new(),
+            trash: Trash::default(),
+            undo: None,
         }
     }
 }
 
 impl iced::Application for Laptev {
     type Executor = iced_futures::backend::native::tokio::Executor;
-    type Flags = ();
+    type Flags = Config;
     type Message = Message;
     type Theme = Theme;
 
-    fn new(_flags: Self::Flags) -> (Self, iced::Command<Self::Message>) {
-        (Self::default(), Command::none())
+    fn new(flags: Self::Flags) -> (Self, iced::Command<Self::Message>) {
+        (Self::from(flags), Command::none())
     }
 
     fn title(&self) -> String {
@@ -340,13 +711,29 @@ impl iced::Application for Laptev {
                 }
             }
             Message::SyncAttempt(result) => match result {
-                Ok(shared_cipher) => {
+                Ok((shared_cipher, connection, identity)) => {
                     self.cipher = Some(shared_cipher.clone());
+                    self.connection = Some(connection.clone());
                     let socket_address = self.get_socket_address().unwrap();
-                    let config = self.config.clone();
+
+                    // pins the server's identity if this was the first connection to it
+                    if let Some(server) = self.config.entries.get_mut(&socket_address.ip()) {
+                        if server.identity.is_none() {
+                            server.identity = Some(identity);
+                            if let Err(error) = self.config.save() {
+                                tracing::warn!("failed to save the pinned identity\n{}", error);
+                            }
+                        }
+                    }
+                    let parameters = SyncRequest {
+                        size: self.config.size,
+                        ..Default::default()
+                    };
 
                     Command::perform(
-                        async move { Self::sync(socket_address, shared_cipher, config).await },
+                        async move {
+                            Self::sync(connection, shared_cipher, parameters, HashMap::new()).await
+                        },
                         Message::SyncOutput,
                     )
                 }
@@ -357,8 +744,13 @@ impl iced::Application for Laptev {
                 }
             },
             Message::SyncOutput(result) => match result {
-                Ok(entries) => {
-                    self.entries.extend(entries.0);
+                Ok(page) => {
+                    for entry in page.entries.iter() {
+                        if let Some(thumbnail) = &entry.thumbnail {
+                            self.thumbnails.insert(entry.timestamp, thumbnail.clone());
+                        }
+                    }
+                    self.page = page;
                     self.mode = Mode::Synced;
                     iced::window::resize(Size::new(1280, 720))
                 }
@@ -368,39 +760,111 @@ impl iced::Application for Laptev {
                     Command::none()
                 }
             },
-            Message::SyncRefresh => {
-                self.entries.clear();
-                self.mode = Mode::Syncing;
-                let socket_address = self.get_socket_address().unwrap();
-                let shared_cipher = self.cipher.as_ref().unwrap().clone();
-                let config = self.config.clone();
-                Command::batch([
-                    iced::window::resize(Size::new(300, 400)),
-                    Command::perform(
-                        async move { Self::sync(socket_address, shared_cipher, config).await },
-                        Message::SyncOutput,
-                    ),
-                ])
-            }
+            Message::SyncRefresh => self.sync_page(SyncRequest {
+                size: self.config.size,
+                ..Default::default()
+            }),
+            Message::SyncPage(parameters) => self.sync_page(parameters),
             Message::Return => {
                 self.clear();
                 iced::window::resize(Size::new(300, 400))
             }
             Message::Download(id) => {
-                let socket_address = self.get_socket_address().unwrap();
+                let connection = self.connection.clone().unwrap();
                 let shared_cipher = self.cipher.clone().unwrap();
                 Command::perform(
-                    Self::download(id, socket_address, shared_cipher),
+                    Self::download(id, connection, shared_cipher),
                     Message::None,
                 )
             }
+            Message::Notification(notification) => self.notify(notification),
+            Message::ThumbnailOutput((id, thumbnail)) => {
+                if let Some(thumbnail) = thumbnail {
+                    if let Some(entry) = self
+                        .page
+                        .entries
+                        .iter_mut()
+                        .find(|entry| entry.timestamp == id)
+                    {
+                        entry.thumbnail = Some(thumbnail.clone());
+                    }
+                    self.thumbnails.insert(id, thumbnail);
+                }
+                Command::none()
+            }
             Message::Delete(id) => {
-                let socket_address = self.get_socket_address().unwrap();
-                Command::perform(Self::delete(id, socket_address), Message::None)
+                let connection = self.connection.clone().unwrap();
+                let shared_cipher = self.cipher.clone().unwrap();
+                Command::perform(
+                    async move { (id, Self::delete(id, connection, shared_cipher).await) },
+                    Message::DeleteOutput,
+                )
+            }
+            Message::DeleteOutput((id, deleted)) => {
+                // only events in the trash can be restored
+                if deleted.is_some_and(|deleted| deleted.trashed()) {
+                    self.undo = Some(id);
+                }
+                Command::none()
+            }
+            Message::Pin(id, pinned) => {
+                let connection = self.connection.clone().unwrap();
+                let shared_cipher = self.cipher.clone().unwrap();
+                Command::perform(
+                    Self::pin(id, pinned, connection, shared_cipher),
+                    Message::None,
+                )
+            }
+            Message::Trash => {
+                let connection = self.connection.clone().unwrap();
+                let shared_cipher = self.cipher.clone().unwrap();
+                Command::perform(Self::trash(connection, shared_cipher), Message::TrashOutput)
+            }
+            Message::TrashOutput(result) => {
+                match result {
+                    // unless we disconnected in the meantime
+                    Ok(trash) if self.connection.is_some() => {
+                        self.trash = trash;
+                        self.mode = Mode::Trash;
+                    }
+                    Ok(_) => (),
+                    Err(error) => tracing::warn!("{}", error),
+                }
+                Command::none()
+            }
+            Message::CloseTrash => {
+                self.mode = Mode::Synced;
+                Command::none()
+            }
+            Message::Restore(id) => {
+                let connection = self.connection.clone().unwrap();
+                let shared_cipher = self.cipher.clone().unwrap();
+                Command::perform(
+                    async move { (id, Self::restore(id, connection, shared_cipher).await) },
+                    Message::RestoreOutput,
+                )
+            }
+            Message::RestoreOutput((id, restored)) => {
+                if restored {
+                    self.trash.remove(id);
+                    if self.undo == Some(id) {
+                        self.undo = None;
+                    }
+                }
+                Command::none()
             }
             Message::None(_) => Command::none(),
         }
     }
+    fn subscription(&self) -> iced::Subscription<Self::Message> {
+        // as long as we have a session, whether a page is being fetched or not
+        match (&self.connection, &self.cipher) {
+            (Some(connection), Some(shared_cipher)) => {
+                notifications::subscribe(connection.clone(), shared_cipher.clone())
+            }
+            _ => iced::Subscription::none(),
+        }
+    }
     fn view(&self) -> iced::Element<'_, Self::Message, iced::Renderer<Self::Theme>> {
         match self.mode {
             Mode::Initial => column![
@@ -440,16 +904,77 @@ impl iced::Application for Laptev {
             .padding(20)
             .spacing(10)
             .into(),
-            Mode::Synced => column![
+            Mode::Synced => {
+                // only navigable towards pages that exist
+                let mut newer = button(text("load newer")).padding(5);
+                if let Some(cursor) = self.page.newer {
+                    newer = newer.on_press(Message::SyncPage(SyncRequest::newer(
+                        self.config.size,
+                        cursor,
+                    )));
+                }
+                let mut older = button(text("load older")).padding(5);
+                if let Some(cursor) = self.page.older {
+                    older = older.on_press(Message::SyncPage(SyncRequest::older(
+                        self.config.size,
+                        cursor,
+                    )));
+                }
+                let role = self
+                    .cipher
+                    .as_ref()
+                    .map_or(Role::Viewer, |cipher| cipher.role());
+                let mut navigation = row![newer, older].spacing(20);
+                if role.allows(Permission::Delete) {
+                    navigation =
+                        navigation.push(button(text("trash")).on_press(Message::Trash).padding(5));
+                }
+                if let Some(id) = self.undo {
+                    navigation = navigation.push(
+                        button(text("undo delete"))
+                            .on_press(Message::Restore(id))
+                            .padding(5),
+                    );
+                }
+
+                column![
+                    row![
+                        button(text("synchronize").horizontal_alignment(alignment::Horizontal::Center))
+                            .on_press(Message::SyncRefresh)
+                            .padding(5),
+                        image(image::Handle::from_memory(ICON_CLEAR))
+                            .width(75)
+                            .height(75),
+                        button(text("disconnect").horizontal_alignment(alignment::Horizontal::Center))
+                            .on_press(Message::Return)
+                            .padding(5),
+                    ]
+                    .padding(10)
+                    .spacing(20)
+                    .align_items(alignment::Alignment::Center),
+                    horizontal_rule(1),
+                    navigation,
+                    scrollable(
+                        container(self.page.entries.to_widget(self.config.local_offset, role))
+                            .width(iced::Length::Fill)
+                            .center_x()
+                    )
+                ]
+                .align_items(alignment::Alignment::Center)
+                .padding(20)
+                .spacing(10)
+                .into()
+            }
+            Mode::Trash => column![
                 row![
-                    button(text("synchronize").horizontal_alignment(alignment::Horizontal::Center))
-                        .on_press(Message::SyncRefresh)
+                    button(text("back").horizontal_alignment(alignment::Horizontal::Center))
+                        .on_press(Message::CloseTrash)
                         .padding(5),
                     image(image::Handle::from_memory(ICON_CLEAR))
                         .width(75)
                         .height(75),
-                    button(text("disconnect").horizontal_alignment(alignment::Horizontal::Center))
-                        .on_press(Message::Return)
+                    button(text("refresh").horizontal_alignment(alignment::Horizontal::Center))
+                        .on_press(Message::Trash)
                         .padding(5),
                 ]
                 .padding(10)
@@ -457,7 +982,7 @@ impl iced::Application for Laptev {
                 .align_items(alignment::Alignment::Center),
                 horizontal_rule(1),
                 scrollable(
-                    container(self.entries.to_widget(self.config.local_offset))
+                    container(self.trash.to_widget(self.config.local_offset))
                         .width(iced::Length::Fill)
                         .center_x()
                 )
@@ -474,12 +999,25 @@ impl iced::Application for Laptev {
 pub enum Message {
     SocketAddrInputUpdate(String),
     SyncEvent,
-    SyncAttempt(error::Result<SharedCipher>),
-    SyncOutput(error::Result<Entries>),
+    SyncAttempt(error::Result<(SharedCipher, Connection, String)>),
+    SyncOutput(error::Result<Page>),
     SyncRefresh,
+    SyncPage(SyncRequest),
+    Notification(Notification),
+    ThumbnailOutput((u64, Option<Thumbnail>)),
     Return,
     Download(u64),
     Delete(u64),
+    // the entry, and what became of its files if it was deleted
+    DeleteOutput((u64, Option<DeleteResponse>)),
+    // the entry to pin, or to unpin if false
+    Pin(u64, bool),
+    Trash,
+    TrashOutput(error::Result<Trash>),
+    CloseTrash,
+    Restore(u64),
+    // the entry, and whether it was restored
+    RestoreOutput((u64, bool)),
     None(()),
 }
 
@@ -487,4 +1025,6 @@ enum Mode {
     Initial,
     Syncing,
     Synced,
+    // the deleted events that can still be restored
+    Trash,
 }
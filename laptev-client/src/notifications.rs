@@ -0,0 +1,77 @@
+use iced::{
+    futures::{channel::mpsc, SinkExt},
+    Subscription,
+};
+use laptev_protocol::{endpoint, notification::Notification, session::SESSION_HEADER};
+use reqwest::{StatusCode, Url};
+use std::{str::FromStr, time::Duration};
+
+use crate::{
+    connection::Connection,
+    data::internal::SharedCipher,
+    error::{self, Error},
+    Message,
+};
+
+// how long to wait before reconnecting once the stream dropped
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// listens to the host's notifications for as long as our session lasts, reconnecting whenever the stream drops
+pub fn subscribe(connection: Connection, cipher: SharedCipher) -> Subscription<Message> {
+    iced::subscription::channel(cipher.session(), 16, move |mut output| async move {
+        loop {
+            match listen(&connection, &cipher, &mut output).await {
+                // our session expired, the next one comes with its own subscription
+                Err(Error::Forbidden) => iced::futures::future::pending::<()>().await,
+                Err(error) => tracing::warn!("{}", error),
+                Ok(()) => (),
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    })
+}
+
+async fn listen(
+    connection: &Connection,
+    cipher: &SharedCipher,
+    output: &mut mpsc::Sender<Message>,
+) -> error::Result<()> {
+    let url: String = connection.url(endpoint::NOTIFICATIONS);
+    let mut response = connection
+        .client()
+        .get(Url::from_str(&url).unwrap())
+        .header(SESSION_HEADER, cipher.session().to_string())
+        .send()
+        .await
+        .map_err(|error| {
+            tracing::warn!("{}", error);
+            Error::ServerNotResponding
+        })?;
+    if response.status() == StatusCode::FORBIDDEN {
+        return Err(Error::Forbidden);
+    }
+
+    // server-sent events end with a blank line, only their data lines matter, the rest are keep-alives
+    let mut buffer: Vec<u8> = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|error| {
+        tracing::warn!("{}", error);
+        Error::ServerNotResponding
+    })? {
+        buffer.extend_from_slice(&chunk);
+        while let Some(end) = buffer.windows(2).position(|window| window == b"\n\n") {
+            let event: Vec<u8> = buffer.drain(..end + 2).collect();
+            for line in String::from_utf8_lossy(&event).lines() {
+                if let Some(data) = line.strip_prefix("data:") {
+                    match Notification::open(data, cipher) {
+                        Ok(notification) => {
+                            // only fails once the app stopped listening
+                            let _ = output.send(Message::Notification(notification)).await;
+                        }
+                        Err(error) => tracing::warn!("received an invalid notification\n{}", error),
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,3 @@
-use rand::{rngs::StdRng, RngCore, SeedableRng};
 use std::time::{Duration, SystemTime};
 
 use iced::{
@@ -15,14 +14,6 @@ pub fn get_timestamp() -> u64 {
         .as_secs()
 }
 
-pub fn get_rng() -> StdRng {
-    rand::rngs::StdRng::from_entropy()
-}
-
-pub fn rng_fill_bytes(bytes: &mut [u8]) {
-    get_rng().fill_bytes(bytes);
-}
-
 pub fn invisible_rule() -> iced::widget::Rule {
     horizontal_rule(1).style(iced::theme::Rule::Custom(Box::new(
         InvisibleHorizontalRuleCustomStyle,
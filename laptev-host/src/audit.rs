@@ -0,0 +1,191 @@
+use laptev_protocol::audit::{AuditEvent, AuditRecord, AuditRequest};
+use std::{
+    io::SeekFrom,
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use crate::utils::get_timestamp;
+
+const AUDIT_FILE: &str = "laptev-audit.log";
+/// how much of the log is read at once when it is queried, in bytes
+const BLOCK_SIZE: u64 = 65536;
+
+/// an append-only record of authentications, downloads and deletions, one JSON object per line,
+/// kept in its own file so that it survives laptev-host.log being started anew
+#[derive(Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    /// the log is kept next to the configuration, in the given directory
+    pub fn new(directory: &Path) -> Self {
+        Self {
+            path: directory.join(AUDIT_FILE),
+        }
+    }
+
+    /// appends a record to the log, failures are logged but never stop the request
+    pub async fn record(&self, addr: IpAddr, username: Option<String>, event: AuditEvent) {
+        let record = AuditRecord {
+            timestamp: get_timestamp(),
+            addr,
+            username,
+            event,
+        };
+        if let Err(error) = self.append(&record).await {
+            tracing::warn!("failed to append to the audit log\n{}", error);
+        }
+    }
+
+    async fn append(&self, record: &AuditRecord) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?
+            .write_all(&line)
+            .await?;
+        Ok(())
+    }
+
+    /// returns the records matching the request, most recent first,
+    /// the log is read backwards one block at a time, only until `limit` records matched
+    pub async fn query(&self, request: &AuditRequest) -> anyhow::Result<Vec<AuditRecord>> {
+        let mut file = match tokio::fs::OpenOptions::new()
+            .create(false)
+            .read(true)
+            .open(&self.path)
+            .await
+        {
+            Ok(file) => file,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        };
+
+        let limit = request.limit.unwrap_or(usize::MAX);
+        let mut records: Vec<AuditRecord> = Vec::new();
+        let mut position = file.metadata().await?.len();
+        // the end of a line that starts in a block yet to be read
+        let mut partial: Vec<u8> = Vec::new();
+        while position > 0 && records.len() < limit {
+            let start = position.saturating_sub(BLOCK_SIZE);
+            let mut block = vec![0; (position - start) as usize];
+            file.seek(SeekFrom::Start(start)).await?;
+            file.read_exact(&mut block).await?;
+            block.append(&mut partial);
+            position = start;
+
+            // unless the beginning of the log was reached, the first line may not be complete
+            let complete = match (position, block.iter().position(|byte| *byte == b'\n')) {
+                (0, _) => 0,
+                (_, Some(newline)) => newline + 1,
+                (_, None) => {
+                    partial = block;
+                    continue;
+                }
+            };
+            records.extend(
+                block[complete..]
+                    .rsplit(|byte| *byte == b'\n')
+                    .filter(|line| !line.is_empty())
+                    .filter_map(parse)
+                    .filter(|record| request.matches(record))
+                    .take(limit - records.len()),
+            );
+            block.truncate(complete.saturating_sub(1));
+            partial = block;
+        }
+        Ok(records)
+    }
+}
+
+/// malformed lines, e.g. one cut short by a crash, are logged and skipped
+fn parse(line: &[u8]) -> Option<AuditRecord> {
+    match serde_json::from_slice::<AuditRecord>(line) {
+        Ok(record) => Some(record),
+        Err(error) => {
+            tracing::warn!("skipped a malformed audit log line\n{}", error);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AuditLog;
+    use laptev_protocol::audit::{AuditEvent, AuditRequest};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    async fn ids(audit: &AuditLog, request: AuditRequest) -> Vec<u64> {
+        audit
+            .query(&request)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter_map(|record| record.event.id())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn query() {
+        let directory = tempfile::tempdir().unwrap();
+        let audit = AuditLog::new(directory.path());
+        assert!(audit
+            .query(&AuditRequest::default())
+            .await
+            .unwrap()
+            .is_empty());
+
+        // spans several blocks
+        for id in 0..2000 {
+            audit
+                .record(
+                    IpAddr::V4(Ipv4Addr::LOCALHOST),
+                    Some(String::from("admin")),
+                    AuditEvent::Delete { id, success: true },
+                )
+                .await;
+        }
+
+        assert_eq!(
+            ids(&audit, AuditRequest::default()).await,
+            (0..2000).rev().collect::<Vec<u64>>()
+        );
+        assert_eq!(
+            ids(
+                &audit,
+                AuditRequest {
+                    limit: Some(3),
+                    ..Default::default()
+                }
+            )
+            .await,
+            vec![1999, 1998, 1997]
+        );
+        assert_eq!(
+            ids(
+                &audit,
+                AuditRequest {
+                    id: Some(7),
+                    ..Default::default()
+                }
+            )
+            .await,
+            vec![7]
+        );
+        assert!(ids(
+            &audit,
+            AuditRequest {
+                until: Some(0),
+                ..Default::default()
+            }
+        )
+        .await
+        .is_empty());
+    }
+}
@@ -0,0 +1,271 @@
+//! the command-line interface of laptev-host, every command but serve only works on the files,
+//! so they need no network and can be run while the host is running (it picks up their changes)
+
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use laptev_protocol::{admin::ReconcileAction, password};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    config::{Account, Config, CONFIG_PATH},
+    data::{index::EventIndex, pins::Pins, storage::Storage},
+    utils,
+};
+
+/// serves the events captured by motioncapture.py to laptev-client
+#[derive(Parser)]
+#[command(version)]
+pub struct Cli {
+    /// the configuration file
+    #[arg(long, global = true, default_value = CONFIG_PATH)]
+    pub config: PathBuf,
+    /// the directory holding the thumbnails and clips of the events, instead of the data_directory of the configuration
+    #[arg(long, global = true)]
+    pub data_dir: Option<PathBuf>,
+    /// where serve writes its log instead of the standard output, it is started anew once it reaches 8 MB, the previous one is kept as <file>.1
+    #[arg(long)]
+    pub log_file: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// runs the server, what laptev-host does when given no command
+    Serve,
+    /// generates a configuration with a new admin account and creates the data directory
+    Init,
+    /// prints the username and password of an account, ready to be pasted in the client's configuration
+    ShowPassword {
+        #[arg(default_value = "admin")]
+        account: String,
+    },
+    /// replaces the password of an account with a new random one, signing out the clients using it
+    RotatePassword {
+        #[arg(default_value = "admin")]
+        account: String,
+    },
+    /// lists the events in the data directory, oldest first
+    ListEvents,
+    /// removes the events older than file_expiration_time, pinned events excepted
+    Prune {
+        /// removes the events older than this many seconds instead
+        #[arg(long)]
+        older_than: Option<u64>,
+    },
+    /// lists the files in the data directory that are not part of a complete event, e.g. a thumbnail without a clip
+    Reconcile {
+        /// moves them to the quarantine subdirectory
+        #[arg(long, conflicts_with = "repair")]
+        quarantine: bool,
+        /// repairs those that can be (e.g. a missing thumbnail is taken from the clip) and quarantines the others
+        #[arg(long)]
+        repair: bool,
+    },
+    /// checks that the configuration loads, without starting anything
+    CheckConfig,
+}
+
+impl Command {
+    /// runs any command but serve
+    pub async fn run(
+        self,
+        config_path: &Path,
+        data_directory: Option<&Path>,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::Serve => unreachable!("serve is run by main"),
+            Self::Init => init(config_path, data_directory).await,
+            Self::ShowPassword { account } => {
+                let (config, _) = Config::load(config_path).await?;
+                let entry = config
+                    .accounts
+                    .get(&account)
+                    .with_context(|| format!("there is no account named {:?}", account))?;
+                println!("{}", client_entry(&account, entry)?);
+                Ok(())
+            }
+            Self::RotatePassword { account } => {
+                let (mut config, _) = Config::load(config_path).await?;
+                let entry = config
+                    .accounts
+                    .get_mut(&account)
+                    .with_context(|| format!("there is no account named {:?}", account))?;
+                *entry = Account::generate(entry.role);
+                let entry = client_entry(&account, entry)?;
+                config.save().await?;
+                println!("{}", entry);
+                Ok(())
+            }
+            Self::ListEvents => {
+                let (config, _) = Config::load(config_path).await?;
+                list_events(&Storage::new(&config, data_directory), config.directory()).await
+            }
+            Self::Prune { older_than } => {
+                let (config, _) = Config::load(config_path).await?;
+                let seconds = older_than.unwrap_or(config.file_expiration_time);
+                prune(
+                    &Storage::new(&config, data_directory),
+                    config.directory(),
+                    seconds,
+                )
+                .await
+            }
+            Self::Reconcile { quarantine, repair } => {
+                let (config, _) = Config::load(config_path).await?;
+                let action = if repair {
+                    ReconcileAction::Repair
+                } else if quarantine {
+                    ReconcileAction::Quarantine
+                } else {
+                    ReconcileAction::Report
+                };
+                reconcile(
+                    &Storage::new(&config, data_directory),
+                    config.directory(),
+                    action,
+                )
+                .await
+            }
+            Self::CheckConfig => {
+                let (config, migrated) = Config::load(config_path).await?;
+                println!("{} is valid", config_path.display());
+                if migrated {
+                    println!("its password will be migrated to the admin account on launch");
+                }
+                print!("{:?}", config);
+                Ok(())
+            }
+        }
+    }
+}
+
+async fn init(config_path: &Path, data_directory: Option<&Path>) -> anyhow::Result<()> {
+    // never replaces the passwords clients already use
+    if tokio::fs::try_exists(config_path).await? {
+        anyhow::bail!(
+            "{} already exists, use rotate-password to replace a password",
+            config_path.display()
+        );
+    }
+    let config = Config::generate(config_path);
+    config
+        .save()
+        .await
+        .with_context(|| format!("failed to write {}", config_path.display()))?;
+    Storage::new(&config, data_directory)
+        .create_directories()
+        .await
+        .context("failed to create the data directory")?;
+    for (name, account) in config.accounts.iter() {
+        println!("{}", client_entry(name, account)?);
+    }
+    Ok(())
+}
+
+/// the account as the value of a host in the `entries` of the client's configuration,
+/// e.g. {"username": "admin", "password": "01d572..."}
+fn client_entry(name: &str, account: &Account) -> anyhow::Result<String> {
+    Ok(format!(
+        "{{\"username\": {}, \"password\": {}}}",
+        serde_json::to_string(name)?,
+        serde_json::to_string(&password::encode(&account.password))?
+    ))
+}
+
+/// the directories of the storage have to exist, a missing directory would simply look empty
+async fn check_directories(storage: &Storage) -> anyhow::Result<()> {
+    for directory in storage.directories() {
+        tokio::fs::metadata(directory)
+            .await
+            .with_context(|| format!("failed to read {}", directory.display()))?;
+    }
+    Ok(())
+}
+
+/// the events in the storage, with the pins kept in the given directory
+async fn load_index(storage: &Storage, directory: &Path) -> anyhow::Result<EventIndex> {
+    check_directories(storage).await?;
+    Ok(EventIndex::load(storage, Pins::load(directory).await?).await)
+}
+
+async fn list_events(storage: &Storage, directory: &Path) -> anyhow::Result<()> {
+    let index = load_index(storage, directory).await?;
+
+    println!(
+        "{:<12}{:<12}{:<12}{:<12}pinned",
+        "timestamp", "thumbnail", "clip", "duration"
+    );
+    let mut count: usize = 0;
+    for (timestamp, event) in index.events() {
+        let clip = if event.recording {
+            String::from("recording")
+        } else {
+            size(event.clip_size)
+        };
+        let duration = event.duration.map_or(String::from("-"), |duration| {
+            format!("{:.1} s", duration as f64 / 1000.0)
+        });
+        println!(
+            "{:<12}{:<12}{:<12}{:<12}{}",
+            timestamp,
+            size(event.thumbnail_size),
+            clip,
+            duration,
+            if index.is_pinned(timestamp) {
+                "yes"
+            } else {
+                "no"
+            }
+        );
+        count += 1;
+    }
+    println!("{} events", count);
+    Ok(())
+}
+
+/// e.g. "4.5 MB", "-" if there is no such file
+fn size(bytes: Option<u64>) -> String {
+    match bytes {
+        None => String::from("-"),
+        Some(bytes) if bytes < 1_000_000 => format!("{:.1} kB", bytes as f64 / 1000.0),
+        Some(bytes) => format!("{:.1} MB", bytes as f64 / 1_000_000.0),
+    }
+}
+
+async fn prune(storage: &Storage, directory: &Path, seconds: u64) -> anyhow::Result<()> {
+    let index = load_index(storage, directory).await?;
+
+    let expired = index.up_to(utils::get_timestamp().saturating_sub(seconds));
+    for timestamp in expired.iter() {
+        storage.remove_event(*timestamp).await;
+    }
+    println!(
+        "removed {} events older than {} seconds",
+        expired.len(),
+        seconds
+    );
+    Ok(())
+}
+
+async fn reconcile(
+    storage: &Storage,
+    directory: &Path,
+    action: ReconcileAction,
+) -> anyhow::Result<()> {
+    check_directories(storage).await?;
+
+    let pins = Pins::load(directory).await?;
+    let findings = crate::reconcile::reconcile(storage, &pins, action).await;
+    println!("{:<20}{:<14}file", "inconsistency", "resolution");
+    for finding in findings.iter() {
+        println!(
+            "{:<20}{:<14}{}",
+            format!("{:?}", finding.inconsistency),
+            format!("{:?}", finding.resolution),
+            finding.path
+        );
+    }
+    println!("{} inconsistencies", findings.len());
+    Ok(())
+}
@@ -1,82 +1,481 @@
+use anyhow::Context;
+use laptev_protocol::{admin::ReconcileAction, role::Role};
+use notify::{RecursiveMode, Watcher};
 use rand::{RngCore, SeedableRng};
 use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
+use crate::{
+    data::{internal::SharedState, storage::DATA_DIRECTORY},
+    utils,
+};
+
+/// where the configuration is looked for, unless told otherwise with --config
+pub const CONFIG_PATH: &str = "laptev.config";
+/// editors usually write a file in several steps, the configuration is only reloaded once they are done
+const RELOAD_DELAY: Duration = Duration::from_millis(500);
+
+/// every field has a default, so that configurations written by older versions still load,
+/// but unknown fields are refused, a misspelled field would otherwise silently be replaced by its default
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
+    #[serde(default = "default_port")]
     pub port: u16,
-    pub password: Vec<u8>,
+    // serves HTTPS with a self-signed certificate instead of plain HTTP
+    #[serde(default)]
+    pub tls: bool,
+    #[serde(default)]
+    pub accounts: BTreeMap<String, Account>,
+    // the single password of older configurations, migrated to an admin account on launch
+    #[serde(default, skip_serializing)]
+    password: Option<Vec<u8>>,
+    #[serde(default = "default_client_expiration_time")]
     pub client_expiration_time: u64,
+    #[serde(default = "default_file_expiration_time")]
     pub file_expiration_time: u64,
+    // how long (in seconds) deleted events stay in the trash before they are purged for good, 0 to delete them right away
+    #[serde(default = "default_trash_expiration_time")]
+    pub trash_expiration_time: u64,
+    // failed handshakes after which an address is banned, and for how many seconds
+    #[serde(default = "default_max_handshake_failures")]
+    pub max_handshake_failures: u32,
+    #[serde(default = "default_ban_duration")]
+    pub ban_duration: u64,
+    // key exchanges that have yet to be followed by an authentication, across all addresses
+    #[serde(default = "default_max_pending_sessions")]
+    pub max_pending_sessions: usize,
+    // where the thumbnails and clips are stored, unless they have directories of their own
+    #[serde(default = "default_data_directory")]
+    pub data_directory: PathBuf,
+    #[serde(default)]
+    pub thumbnail_directory: Option<PathBuf>,
+    #[serde(default)]
+    pub clip_directory: Option<PathBuf>,
+    // the budget of the events, the oldest ones are removed before they expire to stay within it,
+    // the most space (in bytes) they may take and the share of the disk (in percent) to keep free
+    #[serde(default)]
+    pub max_storage_size: Option<u64>,
+    #[serde(default)]
+    pub min_free_space: Option<u8>,
+    // the share of the disk (in percent) under which events are removed until twice that is free, whatever the budget
+    #[serde(default = "default_critical_free_space")]
+    pub critical_free_space: Option<u8>,
+    // the program (and its arguments) capturing motion, run and restarted by the host, empty to run it some other way
+    #[serde(default = "default_capture_command")]
+    pub capture_command: Vec<String>,
+    // what is done on launch with the files of the data directories that are not part of a complete event
+    #[serde(default)]
+    pub reconcile_on_launch: ReconcileAction,
+    // the file this configuration was loaded from, and is saved to
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+fn default_port() -> u16 {
+    12675
+}
+
+fn default_client_expiration_time() -> u64 {
+    1800
+}
+
+fn default_file_expiration_time() -> u64 {
+    259200
+}
+
+fn default_trash_expiration_time() -> u64 {
+    86400
+}
+
+fn default_max_handshake_failures() -> u32 {
+    10
+}
+
+fn default_ban_duration() -> u64 {
+    3600
+}
+
+fn default_max_pending_sessions() -> usize {
+    64
+}
+
+fn default_data_directory() -> PathBuf {
+    PathBuf::from(DATA_DIRECTORY)
+}
+
+fn default_critical_free_space() -> Option<u8> {
+    Some(5)
+}
+
+fn default_capture_command() -> Vec<String> {
+    vec![String::from("./motioncapture.py")]
+}
+
+/// a named account clients can authenticate as
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Account {
+    pub role: Role,
+    #[serde(with = "laptev_protocol::password")]
+    pub password: Vec<u8>,
+}
+
+impl Account {
+    pub fn generate(role: Role) -> Self {
+        let mut password: Vec<u8> = vec![0; 128];
+        rand::rngs::StdRng::from_entropy().fill_bytes(&mut password);
+        Self { role, password }
+    }
 }
 
 impl Config {
-    /// uses Self::load(), Self::generate(), and Self::save() to guarantee a valid configuration is obtained
-    pub async fn new() -> Self {
-        match Self::load().await {
-            Ok(config) => {
-                tracing::info!("configuration loaded from laptev.config");
-                config
+    /// loads the configuration, a new one is only generated (with a new random password) if there is none,
+    /// one that exists but fails to load is never overwritten, the host refuses to start instead
+    pub async fn new(path: &Path) -> anyhow::Result<Self> {
+        match Self::load(path).await {
+            Ok((config, migrated)) => {
+                tracing::info!("configuration loaded from {}", path.display());
+                if migrated {
+                    tracing::info!(
+                        "the password of {} was migrated to the admin account",
+                        path.display()
+                    );
+                    if let Err(error) = config.save().await {
+                        tracing::warn!("failed to save the migrated config\n{}", error);
+                    }
+                }
+                Ok(config)
             }
-            Err(error) => {
-                tracing::warn!("failed to load configuration\n{}", error);
-                let config = Self::generate();
+            Err(error)
+                if error
+                    .downcast_ref::<std::io::Error>()
+                    .is_some_and(|error| error.kind() == std::io::ErrorKind::NotFound) =>
+            {
+                tracing::warn!("no configuration found, generating {}", path.display());
+                let config = Self::generate(path);
                 if let Err(error) = config.save().await {
                     tracing::warn!("failed to save generated config\n{}", error);
                 }
-                config
+                Ok(config)
             }
+            Err(error) => Err(error),
         }
     }
 
-    async fn save(&self) -> anyhow::Result<()> {
+    /// the directory the configuration is in, the host keeps its other files (identity, certificate, pins, audit log) next to it
+    pub fn directory(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new(""))
+    }
+
+    pub async fn save(&self) -> anyhow::Result<()> {
+        // one account per line
+        let accounts = self
+            .accounts
+            .iter()
+            .map(|(name, account)| {
+                Ok(format!(
+                    "    {}: {}",
+                    serde_json::to_string(name)?,
+                    serde_json::to_string(account)?
+                ))
+            })
+            .collect::<anyhow::Result<Vec<String>>>()?
+            .join(",\n");
+
         let serialized_data: String = format!(
-            "{{\n  \"port\": {},\n  \"password\": {},\n  \"client_expiration_time\": {},\n  \"file_expiration_time\": {}\n}}",
+            "{{\n  \"port\": {},\n  \"tls\": {},\n  \"accounts\": {{\n{}\n  }},\n  \"client_expiration_time\": {},\n  \"file_expiration_time\": {},\n  \"trash_expiration_time\": {},\n  \"max_handshake_failures\": {},\n  \"ban_duration\": {},\n  \"max_pending_sessions\": {},\n  \"data_directory\": {},\n  \"thumbnail_directory\": {},\n  \"clip_directory\": {},\n  \"max_storage_size\": {},\n  \"min_free_space\": {},\n  \"critical_free_space\": {},\n  \"capture_command\": {},\n  \"reconcile_on_launch\": {}\n}}",
             serde_json::to_string_pretty(&self.port)?,
-            serde_json::to_string(&self.password)?,
+            serde_json::to_string_pretty(&self.tls)?,
+            accounts,
             serde_json::to_string_pretty(&self.client_expiration_time)?,
             serde_json::to_string_pretty(&self.file_expiration_time)?,
+            serde_json::to_string_pretty(&self.trash_expiration_time)?,
+            serde_json::to_string_pretty(&self.max_handshake_failures)?,
+            serde_json::to_string_pretty(&self.ban_duration)?,
+            serde_json::to_string_pretty(&self.max_pending_sessions)?,
+            serde_json::to_string(&self.data_directory)?,
+            serde_json::to_string(&self.thumbnail_directory)?,
+            serde_json::to_string(&self.clip_directory)?,
+            serde_json::to_string(&self.max_storage_size)?,
+            serde_json::to_string(&self.min_free_space)?,
+            serde_json::to_string(&self.critical_free_space)?,
+            serde_json::to_string(&self.capture_command)?,
+            serde_json::to_string(&self.reconcile_on_launch)?,
         );
 
-        tokio::fs::OpenOptions::new()
+        // written to a temporary file first, the watcher would otherwise reload a half-written configuration,
+        // and a crash halfway through would leave one the host refuses to start with
+        let mut temporary_path = self.path.clone().into_os_string();
+        temporary_path.push(".tmp");
+        let mut file = tokio::fs::OpenOptions::new()
             .create(true)
             .write(true)
             .truncate(true)
-            .open("laptev.config")
-            .await?
-            .write_all(serialized_data.as_bytes())
+            .open(&temporary_path)
             .await?;
+        // it holds the passwords, whoever restricted who can read it expects that to stay
+        if let Ok(metadata) = tokio::fs::metadata(&self.path).await {
+            file.set_permissions(metadata.permissions()).await?;
+        }
+        file.write_all(serialized_data.as_bytes()).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&temporary_path, &self.path).await?;
 
         Ok(())
     }
 
-    async fn load() -> anyhow::Result<Self> {
+    /// also returns whether the configuration was migrated from an older format
+    pub async fn load(path: &Path) -> anyhow::Result<(Self, bool)> {
         let mut buffer: Vec<u8> = Vec::with_capacity(1024);
         tokio::fs::OpenOptions::new()
             .create(false)
             .read(true)
-            .open("laptev.config")
+            .open(path)
             .await?
             .read_to_end(&mut buffer)
             .await?;
-        Ok(serde_json::from_slice(&buffer)?)
+        // serde_json's errors point to the line and column at fault
+        let mut config: Self = serde_json::from_slice(&buffer)
+            .with_context(|| format!("{} is malformed", path.display()))?;
+        config.path = path.to_path_buf();
+        let migrated = config.migrate();
+        config
+            .validate()
+            .with_context(|| format!("{} is invalid", path.display()))?;
+        Ok((config, migrated))
     }
 
-    fn generate() -> Self {
-        let mut password: Vec<u8> = vec![0; 128];
-        rand::rngs::StdRng::from_entropy().fill_bytes(&mut password);
+    /// turns the single password of older configurations into an admin account,
+    /// returns whether there was anything to migrate
+    fn migrate(&mut self) -> bool {
+        match self.password.take() {
+            Some(password) => {
+                self.accounts
+                    .entry(String::from("admin"))
+                    .or_insert(Account {
+                        role: Role::Admin,
+                        password,
+                    });
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.accounts.is_empty() {
+            anyhow::bail!("there are no accounts, clients could never authenticate");
+        }
+        for (name, account) in self.accounts.iter() {
+            if name.is_empty() {
+                anyhow::bail!("an account has an empty name");
+            }
+            if account.password.is_empty() {
+                anyhow::bail!("the account {:?} has an empty password", name);
+            }
+        }
+        if self.client_expiration_time == 0 {
+            anyhow::bail!("client_expiration_time has to be at least 1 second");
+        }
+        if self.file_expiration_time == 0 {
+            anyhow::bail!("file_expiration_time has to be at least 1 second");
+        }
+        if self.max_handshake_failures == 0 {
+            anyhow::bail!("max_handshake_failures has to be at least 1");
+        }
+        if self.max_pending_sessions == 0 {
+            anyhow::bail!("max_pending_sessions has to be at least 1");
+        }
+        let directories = [
+            Some(&self.data_directory),
+            self.thumbnail_directory.as_ref(),
+            self.clip_directory.as_ref(),
+        ];
+        if directories
+            .into_iter()
+            .flatten()
+            .any(|directory| directory.as_os_str().is_empty())
+        {
+            anyhow::bail!("a data directory is empty, use \".\" for the working directory");
+        }
+        if self.max_storage_size == Some(0) {
+            anyhow::bail!("max_storage_size has to be at least 1 byte, or null for no limit");
+        }
+        if self
+            .min_free_space
+            .is_some_and(|min_free_space| min_free_space >= 100)
+        {
+            anyhow::bail!("min_free_space has to be less than 100 (percent)");
+        }
+        if self
+            .critical_free_space
+            .is_some_and(|critical_free_space| critical_free_space > 50)
+        {
+            anyhow::bail!("critical_free_space has to be at most 50 (percent)");
+        }
+        Ok(())
+    }
+
+    /// puts back the settings that only take effect on launch, warning about the ones that were changed
+    fn keep_launch_settings(&mut self, running: &Config) {
+        if self.port != running.port {
+            tracing::warn!("the port changed, restart laptev-host to apply it");
+            self.port = running.port;
+        }
+        if self.tls != running.tls {
+            tracing::warn!("tls changed, restart laptev-host to apply it");
+            self.tls = running.tls;
+        }
+        if self.data_directory != running.data_directory
+            || self.thumbnail_directory != running.thumbnail_directory
+            || self.clip_directory != running.clip_directory
+        {
+            tracing::warn!("the data directories changed, restart laptev-host to apply them");
+            self.data_directory = running.data_directory.clone();
+            self.thumbnail_directory = running.thumbnail_directory.clone();
+            self.clip_directory = running.clip_directory.clone();
+        }
+        if self.capture_command != running.capture_command {
+            tracing::warn!("the capture command changed, restart laptev-host to apply it");
+            self.capture_command = running.capture_command.clone();
+        }
+    }
+
+    /// the accounts that were removed, or whose password or role changed
+    pub fn changed_accounts(&self, previous: &Config) -> Vec<String> {
+        previous
+            .accounts
+            .iter()
+            .filter(|(name, account)| self.accounts.get(*name) != Some(*account))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    pub fn generate(path: &Path) -> Self {
+        let mut accounts = BTreeMap::new();
+        accounts.insert(String::from("admin"), Account::generate(Role::Admin));
         Self {
-            port: 12675,
-            password,
-            client_expiration_time: 1800,
-            file_expiration_time: 259200,
+            port: default_port(),
+            tls: false,
+            accounts,
+            password: None,
+            client_expiration_time: default_client_expiration_time(),
+            file_expiration_time: default_file_expiration_time(),
+            trash_expiration_time: default_trash_expiration_time(),
+            max_handshake_failures: default_max_handshake_failures(),
+            ban_duration: default_ban_duration(),
+            max_pending_sessions: default_max_pending_sessions(),
+            data_directory: default_data_directory(),
+            thumbnail_directory: None,
+            clip_directory: None,
+            max_storage_size: None,
+            min_free_space: None,
+            critical_free_space: default_critical_free_space(),
+            capture_command: default_capture_command(),
+            reconcile_on_launch: ReconcileAction::default(),
+            path: path.to_path_buf(),
         }
     }
 }
 
 impl Debug for Config {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "port = {}\npassword = {:?}\n", self.port, self.password)
+        let accounts = self
+            .accounts
+            .iter()
+            .map(|(name, account)| format!("{} ({:?})", name, account.role))
+            .collect::<Vec<String>>()
+            .join(", ");
+        writeln!(
+            f,
+            "port = {}\ntls = {}\naccounts = {}\n",
+            self.port, self.tls, accounts
+        )
+    }
+}
+
+/// reloads the configuration whenever it is written to, or when the host receives SIGHUP
+pub fn watch(state: SharedState, path: &Path) -> notify::Result<()> {
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<()>();
+    let file_name = path.file_name().map(|file_name| file_name.to_os_string());
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let config_sender = sender.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        // the whole directory is watched, editors often replace the file rather than write to it
+        if let Ok(event) = event {
+            if event
+                .paths
+                .iter()
+                .any(|path| path.file_name() == file_name.as_deref())
+            {
+                let _ = config_sender.send(());
+            }
+        }
+    })?;
+    watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+
+    #[cfg(unix)]
+    {
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                tracing::info!("received SIGHUP");
+                let _ = sender.send(());
+            }
+        });
+    }
+
+    tokio::spawn(async move {
+        // the watcher stops as soon as it is dropped
+        let _watcher = watcher;
+        while receiver.recv().await.is_some() {
+            tokio::time::sleep(RELOAD_DELAY).await;
+            while receiver.try_recv().is_ok() {}
+            reload(&state).await;
+        }
+    });
+    Ok(())
+}
+
+/// applies the configuration found on disk, keeps the current one if it cannot be loaded
+async fn reload(state: &SharedState) {
+    let path = state.read().await.config.path.clone();
+    let mut config = match Config::load(&path).await {
+        Ok((config, _)) => config,
+        Err(error) => {
+            tracing::warn!(
+                "failed to reload the configuration, keeping the current one\n{}",
+                error
+            );
+            return;
+        }
+    };
+
+    let mut write_state = state.write().await;
+    config.keep_launch_settings(&write_state.config);
+    let shorter_retention = config.file_expiration_time < write_state.config.file_expiration_time;
+    let shorter_trash_retention =
+        config.trash_expiration_time < write_state.config.trash_expiration_time;
+    write_state.reload(config);
+    tracing::info!("configuration reloaded from {}", path.display());
+    drop(write_state);
+
+    // the hourly cleanup would otherwise keep expired files around for up to an hour
+    if shorter_retention {
+        utils::clean_older_than(state).await;
+    }
+    if shorter_trash_retention {
+        utils::empty_trash(state).await;
     }
 }
@@ -0,0 +1,554 @@
+//! an in-memory index of the events in the storage, built once on launch and kept current by a filesystem watcher,
+//! so that listing, paginating and cleaning up events never has to walk the directories,
+//! every change is also broadcast to the clients listening to `endpoint::NOTIFICATIONS`
+
+use laptev_protocol::{list::EventMetadata, notification::Notification, sync::SyncRequest};
+use notify::{RecursiveMode, Watcher};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    ops::Bound,
+    path::{Path, PathBuf},
+};
+
+use tokio::sync::broadcast;
+
+use super::{
+    internal::SharedState,
+    pins::Pins,
+    storage::{Kind, Probe, Storage},
+};
+
+/// how many notifications a slow client may fall behind by before missing some
+const NOTIFICATION_CAPACITY: usize = 64;
+
+/// what is known about the files of a single event
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Event {
+    pub thumbnail_size: Option<u64>,
+    pub clip_size: Option<u64>,
+    // in milliseconds, None if the clip could not be parsed
+    pub duration: Option<u64>,
+    // the clip is still being written to `<timestamp>.recording.mp4`
+    pub recording: bool,
+}
+
+impl Event {
+    /// the space taken by the files of the event, in bytes
+    pub fn size(&self) -> u64 {
+        self.thumbnail_size.unwrap_or(0) + self.clip_size.unwrap_or(0)
+    }
+    fn is_empty(&self) -> bool {
+        self.thumbnail_size.is_none() && self.clip_size.is_none() && !self.recording
+    }
+}
+
+#[derive(Debug)]
+pub struct EventIndex {
+    events: BTreeMap<u64, Event>,
+    // kept apart from the events, an event keeps its pin while its files come and go
+    pins: Pins,
+    notifications: broadcast::Sender<Notification>,
+}
+
+impl EventIndex {
+    /// an empty index, filled in by watch()
+    pub fn new(pins: Pins) -> Self {
+        Self {
+            events: BTreeMap::new(),
+            pins,
+            notifications: broadcast::channel(NOTIFICATION_CAPACITY).0,
+        }
+    }
+
+    /// indexes the storage once, without watching it
+    pub async fn load(storage: &Storage, pins: Pins) -> Self {
+        Self {
+            events: Self::build(storage).await,
+            ..Self::new(pins)
+        }
+    }
+
+    /// walks the storage once, only used on launch and if the watcher lost track of what happened
+    async fn build(storage: &Storage) -> BTreeMap<u64, Event> {
+        let mut index = Self::new(Pins::default());
+        for path in storage.files().await.iter() {
+            if let Some(probe) = storage.probe(path).await {
+                index.apply(probe);
+            }
+        }
+        index.events
+    }
+
+    pub fn metadata(&self, timestamp: u64, event: &Event) -> EventMetadata {
+        EventMetadata {
+            timestamp,
+            thumbnail_size: event.thumbnail_size,
+            clip_size: event.clip_size,
+            duration: event.duration,
+            recording: event.recording,
+            pinned: self.is_pinned(timestamp),
+        }
+    }
+
+    pub fn contains(&self, timestamp: u64) -> bool {
+        self.events.contains_key(&timestamp)
+    }
+
+    pub fn is_pinned(&self, timestamp: u64) -> bool {
+        self.pins.contains(timestamp)
+    }
+
+    pub fn pins(&self) -> &Pins {
+        &self.pins
+    }
+
+    /// every event, oldest first
+    pub fn events(&self) -> impl Iterator<Item = (u64, &Event)> {
+        self.events
+            .iter()
+            .map(|(timestamp, event)| (*timestamp, event))
+    }
+
+    /// the changes to come, as they happen
+    pub fn subscribe(&self) -> broadcast::Receiver<Notification> {
+        self.notifications.subscribe()
+    }
+
+    fn apply(&mut self, probe: Probe) {
+        self.apply_all(probe.timestamp, vec![probe]);
+    }
+
+    /// applies the probes of several files of an event at once, e.g. when it is restored from the trash,
+    /// so that clients are told about all of them in a single notification
+    pub fn apply_all(&mut self, timestamp: u64, probes: Vec<Probe>) {
+        let before = self.events.get(&timestamp).copied();
+        let event = self.events.entry(timestamp).or_default();
+        for probe in probes.into_iter() {
+            match probe.kind {
+                Kind::Thumbnail => event.thumbnail_size = probe.size,
+                Kind::Clip => {
+                    event.clip_size = probe.size;
+                    event.duration = probe.duration;
+                }
+                Kind::Recording => event.recording = probe.size.is_some(),
+            }
+        }
+        let after = (!event.is_empty()).then_some(*event);
+        if after.is_none() {
+            self.events.remove(&timestamp);
+        }
+        self.notify(timestamp, before, after);
+    }
+
+    /// replaces the events with those of a new walk of the storage, e.g. after the watcher lost track of what happened,
+    /// clients are told about the differences as if they had been watched
+    fn replace(&mut self, events: BTreeMap<u64, Event>) {
+        let before = std::mem::replace(&mut self.events, events);
+        let timestamps: BTreeSet<u64> = before.keys().chain(self.events.keys()).copied().collect();
+        for timestamp in timestamps.into_iter() {
+            self.notify(
+                timestamp,
+                before.get(&timestamp).copied(),
+                self.events.get(&timestamp).copied(),
+            );
+        }
+    }
+
+    /// forgets an event whose files were just removed, without waiting for the watcher to notice
+    pub fn remove(&mut self, timestamp: u64) {
+        let before = self.events.remove(&timestamp);
+        self.notify(timestamp, before, None);
+    }
+
+    /// only a few changes are worth telling clients about, sizes growing as files are written are not
+    fn notify(&self, timestamp: u64, before: Option<Event>, after: Option<Event>) {
+        let notification = match (before, after) {
+            (None, Some(after)) => Notification::New(self.metadata(timestamp, &after)),
+            (Some(before), Some(after))
+                if before.clip_size.is_none() && after.clip_size.is_some() =>
+            {
+                Notification::Completed(self.metadata(timestamp, &after))
+            }
+            (Some(..), None) => Notification::Deleted(timestamp),
+            _ => return,
+        };
+        // only fails if no client is listening
+        let _ = self.notifications.send(notification);
+    }
+
+    /// the timestamps of the events captured at or before the given timestamp, pinned events excepted
+    pub fn up_to(&self, timestamp: u64) -> Vec<u64> {
+        self.events
+            .range(..=timestamp)
+            .map(|(timestamp, _)| *timestamp)
+            .filter(|timestamp| !self.is_pinned(*timestamp))
+            .collect()
+    }
+
+    /// the requested page of the events matching the filter (most recent first), along with the cursors to the older and newer pages,
+    /// cursors are timestamps so pages do not shift when new events land
+    pub fn page(
+        &self,
+        parameters: &SyncRequest,
+        filter: impl Fn(&Event) -> bool,
+    ) -> (Vec<(u64, Event)>, Option<u64>, Option<u64>) {
+        if let (Some(after), Some(before)) = (parameters.after, parameters.before) {
+            if after >= before {
+                return (Vec::new(), None, None);
+            }
+        }
+        let bounds = (
+            parameters.after.map_or(Bound::Unbounded, Bound::Excluded),
+            parameters.before.map_or(Bound::Unbounded, Bound::Excluded),
+        );
+        let matching =
+            |(timestamp, event): (&u64, &Event)| filter(event).then_some((*timestamp, *event));
+
+        let page: Vec<(u64, Event)> = if parameters.from_oldest() {
+            let mut page: Vec<(u64, Event)> = self
+                .events
+                .range(bounds)
+                .filter_map(matching)
+                .take(parameters.size)
+                .collect();
+            page.reverse();
+            page
+        } else {
+            self.events
+                .range(bounds)
+                .rev()
+                .filter_map(matching)
+                .take(parameters.size)
+                .collect()
+        };
+
+        let older = page
+            .last()
+            .map(|(timestamp, _)| *timestamp)
+            .filter(|timestamp| {
+                self.events
+                    .range(..*timestamp)
+                    .any(|(_, event)| filter(event))
+            });
+        let newer = page
+            .first()
+            .map(|(timestamp, _)| *timestamp)
+            .filter(|timestamp| {
+                self.events
+                    .range((Bound::Excluded(*timestamp), Bound::Unbounded))
+                    .any(|(_, event)| filter(event))
+            });
+        (page, older, newer)
+    }
+}
+
+/// changes the pins and saves them, returns the timestamps whose pin changed,
+/// the state is only locked to read the pins and to swap in the changed ones, never while they are written to disk
+pub async fn update_pins(
+    state: &SharedState,
+    change: impl FnOnce(&mut Pins) -> Vec<u64>,
+) -> anyhow::Result<Vec<u64>> {
+    let updating = state.read().await.index.pins.updating.clone();
+    let _updating = updating.lock().await;
+
+    let mut pins = state.read().await.index.pins.clone();
+    let changed = change(&mut pins);
+    if changed.is_empty() {
+        return Ok(changed);
+    }
+    pins.save().await?;
+
+    let mut write_state = state.write().await;
+    let index = &mut write_state.index;
+    index.pins = pins;
+    for timestamp in changed.iter() {
+        if let Some(event) = index.events.get(timestamp) {
+            // only fails if no client is listening
+            let _ = index
+                .notifications
+                .send(Notification::Updated(index.metadata(*timestamp, event)));
+        }
+    }
+    Ok(changed)
+}
+
+/// drops the pins of events that are gone for good, failures are only logged, a leftover pin is harmless
+pub async fn unpin(state: &SharedState, timestamps: &[u64]) {
+    let result = update_pins(state, |pins| {
+        timestamps
+            .iter()
+            .copied()
+            .filter(|timestamp| pins.set(*timestamp, false))
+            .collect()
+    })
+    .await;
+    if let Err(error) = result {
+        tracing::warn!("failed to save the pins\n{}", error);
+    }
+}
+
+/// starts watching the storage, then (re)builds the index, so that no event is missed in between
+pub async fn watch(state: SharedState) -> notify::Result<()> {
+    let storage = state.read().await.storage.clone();
+    storage.create_directories().await?;
+
+    let (sender, mut receiver) =
+        tokio::sync::mpsc::unbounded_channel::<notify::Result<notify::Event>>();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        // the receiver only goes away with the watcher
+        let _ = sender.send(event);
+    })?;
+    // the watcher reports absolute paths, whatever the directories are configured as
+    let mut watched: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for directory in storage.directories() {
+        watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        watched.push((
+            tokio::fs::canonicalize(directory).await?,
+            directory.to_path_buf(),
+        ));
+    }
+
+    // replaces the events only, clients already listening keep their subscription
+    let events = EventIndex::build(&storage).await;
+    tracing::info!("indexed {} events", events.len());
+    state.write().await.index.replace(events);
+
+    tokio::spawn(async move {
+        // the watcher stops as soon as it is dropped
+        let _watcher = watcher;
+        while let Some(event) = receiver.recv().await {
+            // a single clip being written causes a burst of notifications, each file is only probed once per burst
+            let mut paths: HashSet<PathBuf> = HashSet::new();
+            let mut rescan = false;
+            let pending = std::iter::from_fn(|| receiver.try_recv().ok());
+            for event in std::iter::once(event).chain(pending) {
+                match event {
+                    Ok(event) if event.need_rescan() => rescan = true,
+                    Ok(event) => paths.extend(event.paths),
+                    Err(error) => {
+                        tracing::warn!("the data directory watcher failed\n{}", error);
+                        rescan = true;
+                    }
+                }
+            }
+
+            if rescan {
+                let events = EventIndex::build(&storage).await;
+                state.write().await.index.replace(events);
+                continue;
+            }
+            // probes outside of the lock, the disk may be slow
+            let mut probes: Vec<Probe> = Vec::new();
+            for path in paths.iter() {
+                let Some(path) = relocate(&watched, path).await else {
+                    continue;
+                };
+                if let Some(probe) = storage.probe(&path).await {
+                    probes.push(probe);
+                }
+            }
+            let mut write_state = state.write().await;
+            for probe in probes.into_iter() {
+                write_state.index.apply(probe);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// the path of a file reported by the watcher, as the storage knows it
+async fn relocate(watched: &[(PathBuf, PathBuf)], path: &Path) -> Option<PathBuf> {
+    let parent = tokio::fs::canonicalize(path.parent()?).await.ok()?;
+    let (_, directory) = watched.iter().find(|(canonical, _)| *canonical == parent)?;
+    Some(directory.join(path.file_name()?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Event, EventIndex};
+    use crate::data::{
+        pins::Pins,
+        storage::{Kind, Probe},
+    };
+    use laptev_protocol::{notification::Notification, sync::SyncRequest};
+
+    fn probe(timestamp: u64, kind: Kind, size: Option<u64>) -> Probe {
+        Probe {
+            timestamp,
+            kind,
+            size,
+            duration: None,
+        }
+    }
+
+    fn timestamps(page: &[(u64, Event)]) -> Vec<u64> {
+        page.iter().map(|(timestamp, _)| *timestamp).collect()
+    }
+
+    #[test]
+    fn page() {
+        let mut index = EventIndex::new(Pins::default());
+        let all = |_: &Event| true;
+        let (page, older, newer) = index.page(&SyncRequest::default(), all);
+        assert!(page.is_empty() && older.is_none() && newer.is_none());
+
+        for timestamp in [10, 20, 30, 40, 50] {
+            index.apply(probe(timestamp, Kind::Thumbnail, Some(1)));
+            // only 20 and 40 are missing their clip
+            if timestamp % 20 != 0 {
+                index.apply(probe(timestamp, Kind::Clip, Some(1)));
+            }
+        }
+
+        // the most recent events, without cursors
+        let (page, older, newer) = index.page(&SyncRequest::older(2, u64::MAX), all);
+        assert_eq!(
+            (timestamps(&page), older, newer),
+            (vec![50, 40], Some(40), None)
+        );
+        let (page, older, newer) = index.page(
+            &SyncRequest {
+                size: 2,
+                ..Default::default()
+            },
+            all,
+        );
+        assert_eq!(
+            (timestamps(&page), older, newer),
+            (vec![50, 40], Some(40), None)
+        );
+
+        // older pages, down to the edge
+        let (page, older, newer) = index.page(&SyncRequest::older(2, 40), all);
+        assert_eq!(
+            (timestamps(&page), older, newer),
+            (vec![30, 20], Some(20), Some(30))
+        );
+        let (page, older, newer) = index.page(&SyncRequest::older(2, 20), all);
+        assert_eq!(
+            (timestamps(&page), older, newer),
+            (vec![10], None, Some(10))
+        );
+        let (page, older, newer) = index.page(&SyncRequest::older(2, 10), all);
+        assert!(page.is_empty() && older.is_none() && newer.is_none());
+
+        // newer pages start right after the cursor, and are still most recent first
+        let (page, older, newer) = index.page(&SyncRequest::newer(2, 10), all);
+        assert_eq!(
+            (timestamps(&page), older, newer),
+            (vec![30, 20], Some(20), Some(30))
+        );
+        let (page, older, newer) = index.page(&SyncRequest::newer(2, 30), all);
+        assert_eq!(
+            (timestamps(&page), older, newer),
+            (vec![50, 40], Some(40), None)
+        );
+        let (page, older, newer) = index.page(&SyncRequest::newer(2, 50), all);
+        assert!(page.is_empty() && older.is_none() && newer.is_none());
+
+        // both cursors, the most recent events between them first
+        let both = |after, before| SyncRequest {
+            size: 2,
+            before: Some(before),
+            after: Some(after),
+        };
+        let (page, older, newer) = index.page(&both(10, 50), all);
+        assert_eq!(
+            (timestamps(&page), older, newer),
+            (vec![40, 30], Some(30), Some(40))
+        );
+        let (page, older, newer) = index.page(&both(30, 30), all);
+        assert!(page.is_empty() && older.is_none() && newer.is_none());
+        let (page, older, newer) = index.page(&both(40, 30), all);
+        assert!(page.is_empty() && older.is_none() && newer.is_none());
+
+        // cursors only point to pages holding events that match the filter
+        let with_clip = |event: &Event| event.clip_size.is_some();
+        let (page, older, newer) = index.page(&SyncRequest::older(2, u64::MAX), with_clip);
+        assert_eq!(
+            (timestamps(&page), older, newer),
+            (vec![50, 30], Some(30), None)
+        );
+        let (page, older, newer) = index.page(&SyncRequest::older(2, 30), with_clip);
+        assert_eq!(
+            (timestamps(&page), older, newer),
+            (vec![10], None, Some(10))
+        );
+    }
+
+    #[test]
+    fn notifications() {
+        let mut index = EventIndex::new(Pins::default());
+        let mut receiver = index.subscribe();
+
+        // the thumbnail is written first, then the clip is recorded
+        index.apply(probe(10, Kind::Thumbnail, Some(0)));
+        assert!(
+            matches!(receiver.try_recv(), Ok(Notification::New(metadata)) if metadata.timestamp == 10)
+        );
+        index.apply(probe(10, Kind::Thumbnail, Some(2048)));
+        index.apply(probe(10, Kind::Recording, Some(0)));
+        index.apply(probe(10, Kind::Recording, Some(4096)));
+        assert!(receiver.try_recv().is_err());
+        index.apply_all(
+            10,
+            vec![
+                probe(10, Kind::Recording, None),
+                probe(10, Kind::Clip, Some(4096)),
+            ],
+        );
+        assert!(
+            matches!(receiver.try_recv(), Ok(Notification::Completed(metadata)) if metadata.timestamp == 10 && !metadata.recording)
+        );
+        index.apply(probe(10, Kind::Clip, Some(8192)));
+        assert!(receiver.try_recv().is_err());
+
+        // the event is only deleted once its last file is
+        index.apply(probe(10, Kind::Thumbnail, None));
+        assert!(receiver.try_recv().is_err());
+        index.apply(probe(10, Kind::Clip, None));
+        assert!(matches!(receiver.try_recv(), Ok(Notification::Deleted(10))));
+        assert!(!index.contains(10));
+
+        // a restored event arrives whole, in a single notification
+        index.apply_all(
+            20,
+            vec![
+                probe(20, Kind::Thumbnail, Some(2048)),
+                probe(20, Kind::Clip, Some(4096)),
+            ],
+        );
+        assert!(
+            matches!(receiver.try_recv(), Ok(Notification::New(metadata)) if metadata.clip_size == Some(4096))
+        );
+        assert!(receiver.try_recv().is_err());
+
+        // removing an event that is not indexed, or a file that never existed, tells nobody anything
+        index.remove(20);
+        assert!(matches!(receiver.try_recv(), Ok(Notification::Deleted(20))));
+        index.remove(20);
+        index.apply(probe(30, Kind::Clip, None));
+        assert!(receiver.try_recv().is_err());
+        assert!(!index.contains(30));
+
+        // a new walk of the storage is told about like the changes it missed
+        index.apply(probe(40, Kind::Thumbnail, Some(2048)));
+        index.apply(probe(50, Kind::Thumbnail, Some(2048)));
+        index.apply(probe(60, Kind::Thumbnail, Some(2048)));
+        while receiver.try_recv().is_ok() {}
+        let mut walked = EventIndex::new(Pins::default());
+        walked.apply(probe(50, Kind::Thumbnail, Some(4096)));
+        walked.apply(probe(60, Kind::Thumbnail, Some(2048)));
+        walked.apply(probe(60, Kind::Clip, Some(4096)));
+        walked.apply(probe(70, Kind::Thumbnail, Some(2048)));
+        index.replace(walked.events);
+        assert!(matches!(receiver.try_recv(), Ok(Notification::Deleted(40))));
+        assert!(
+            matches!(receiver.try_recv(), Ok(Notification::Completed(metadata)) if metadata.timestamp == 60)
+        );
+        assert!(
+            matches!(receiver.try_recv(), Ok(Notification::New(metadata)) if metadata.timestamp == 70)
+        );
+        assert!(receiver.try_recv().is_err());
+    }
+}
@@ -1,35 +1,180 @@
-use aes_gcm_siv::{Aes256GcmSiv, KeyInit};
-use std::{collections::HashMap, fmt::Debug, net::IpAddr, sync::Arc};
+use aes_gcm_siv::Aes256GcmSiv;
+use laptev_protocol::{
+    action::{Action, ActionRequest},
+    pake::{Confirmation, SessionKeys},
+    role::{Permission, Role},
+    session::SessionId,
+    EncryptedMessage,
+};
+use std::{collections::HashMap, fmt::Debug, net::IpAddr, path::Path, sync::Arc};
 use tokio::sync::RwLock;
 
-use crate::{config::Config, utils::get_timestamp};
+use super::{index::EventIndex, pins::Pins, storage::Storage};
+use crate::{
+    audit::AuditLog, config::Config, error::Error, identity::Identity, supervisor::Capture,
+    throttle::Throttle, utils::get_timestamp,
+};
 
 pub type SharedState = Arc<RwLock<AppState>>;
 
+/// how long a client has to authenticate after its key exchange, in seconds
+const PENDING_EXPIRATION_TIME: u64 = 30;
+/// how far below the highest sequence number a session sent an action may be, actions sent concurrently
+/// may arrive out of order, older ones are refused as it can no longer be told whether they were already sent
+const SEQUENCE_WINDOW: u64 = 64;
+
 pub struct AppState {
     pub config: Config,
-    pub db: HashMap<IpAddr, ClientData>,
+    pub identity: Identity,
+    pub audit: AuditLog,
+    pub throttle: Throttle,
+    // where the files of the events are, only set on launch
+    pub storage: Storage,
+    // the events in the storage, filled in by index::watch()
+    pub index: EventIndex,
+    // the state of the capture process, filled in by supervisor::supervise()
+    pub capture: Capture,
+    pub db: HashMap<SessionId, ClientData>,
 }
 
 impl AppState {
-    pub async fn new() -> Self {
-        AppState {
-            config: Config::new().await,
+    pub async fn new(config_path: &Path, data_directory: Option<&Path>) -> anyhow::Result<Self> {
+        let config = Config::new(config_path).await?;
+        Ok(AppState {
+            storage: Storage::new(&config, data_directory),
+            identity: Identity::new(config.directory()).await?,
+            audit: AuditLog::new(config.directory()),
+            throttle: Throttle::default(),
+            index: EventIndex::new(Pins::load(config.directory()).await?),
+            config,
+            capture: Capture::default(),
             db: HashMap::new(),
-        }
+        })
     }
-    /// removes a client if it has expired
-    /// OPTIONAL TODO: reloads the config if it has changed
+    /// removes a client if it has expired, clients that have yet to authenticate expire much sooner
     pub fn update(&mut self) {
         let current_time = get_timestamp();
 
+        self.db.retain(|_, value| {
+            let expiration_time = if value.is_authenticated() {
+                self.config.client_expiration_time
+            } else {
+                PENDING_EXPIRATION_TIME
+            };
+            value.timestamp + expiration_time > current_time
+        });
+        self.throttle.update(self.config.ban_duration);
+    }
+
+    /// applies a reloaded configuration, the sessions of accounts that were removed,
+    /// or whose password or role changed, are invalidated
+    pub fn reload(&mut self, config: Config) {
+        let changed_accounts = config.changed_accounts(&self.config);
+        let sessions = self.db.len();
+        self.db
+            .retain(|_, value| !changed_accounts.contains(&value.username));
+        if !changed_accounts.is_empty() {
+            tracing::info!(
+                "accounts changed: {}, {} sessions invalidated",
+                changed_accounts.join(", "),
+                sessions - self.db.len()
+            );
+        }
+        self.config = config;
+    }
+
+    /// the amount of clients that performed a key exchange but have yet to authenticate
+    pub fn pending_sessions(&self) -> usize {
         self.db
-            .retain(|_, value| value.timestamp + self.config.client_expiration_time > current_time)
+            .values()
+            .filter(|value| !value.is_authenticated())
+            .count()
     }
 
-    pub fn add_client(&mut self, addr: IpAddr, key: &[u8; 32]) {
+    /// registers a new, not yet authenticated, client and returns the session id it was issued
+    pub fn add_client(
+        &mut self,
+        addr: IpAddr,
+        username: String,
+        role: Role,
+        keys: &SessionKeys,
+    ) -> SessionId {
         self.update();
-        self.db.insert(addr, ClientData::new(key));
+        let mut session = SessionId::generate();
+        while self.db.contains_key(&session) {
+            session = SessionId::generate();
+        }
+        self.db
+            .insert(session, ClientData::new(addr, username, role, keys));
+        session
+    }
+
+    /// returns the data of the client identified by this session, only if it is authenticated
+    pub fn get_authenticated(&self, session: &SessionId) -> Result<&ClientData, Error> {
+        match self.db.get(session) {
+            Some(data) if data.is_authenticated() => Ok(data),
+            _ => Err(Error::NotAuthenticated),
+        }
+    }
+
+    /// returns the data of the client identified by this session,
+    /// only if it is authenticated and its role allows the given permission
+    pub fn get_authorized(
+        &self,
+        session: &SessionId,
+        permission: Permission,
+    ) -> Result<&ClientData, Error> {
+        let data = self.get_authenticated(session)?;
+        if data.role.allows(permission) {
+            Ok(data)
+        } else {
+            tracing::warn!(
+                "{} ({:?}) is not allowed to {:?}",
+                data.username,
+                data.role,
+                permission
+            );
+            Err(Error::NotAuthorized)
+        }
+    }
+
+    /// like get_authorized, for the requests that change something: their body has to be the given action,
+    /// sealed with the session's cipher under a sequence number the session did not use yet
+    pub fn get_authorized_action(
+        &mut self,
+        session: &SessionId,
+        permission: Permission,
+        action: Action,
+        body: &[u8],
+    ) -> Result<&ClientData, Error> {
+        self.get_authorized(session, permission)?;
+        let data = self.db.get_mut(session).ok_or(Error::NotAuthenticated)?;
+        let request = EncryptedMessage::try_from_bytes(body)
+            .and_then(|message| message.try_decrypt(&data.cipher))
+            .and_then(|request| Ok(bincode::deserialize::<ActionRequest>(&request)?));
+        match request {
+            Ok(request) if request.action == action && data.sequences.accept(request.sequence) => {
+                Ok(data)
+            }
+            Ok(request) => {
+                tracing::warn!(
+                    "{} sent {:?} (sequence {}) along with a request to {:?}, or sent it before",
+                    data.username,
+                    request.action,
+                    request.sequence,
+                    action
+                );
+                Err(Error::InvalidAction)
+            }
+            Err(..) => {
+                tracing::warn!(
+                    "{} sent a request to {:?} that is not sealed with the session's cipher",
+                    data.username,
+                    action
+                );
+                Err(Error::InvalidAction)
+            }
+        }
     }
 }
 
@@ -38,7 +183,12 @@ impl Debug for AppState {
         let client_data = self
             .db
             .iter()
-            .map(|(addr, data)| format!("address = {}\ntimestamp = {}\n", addr, data.timestamp))
+            .map(|(session, data)| {
+                format!(
+                    "session = {}\nusername = {}\naddress = {}\ntimestamp = {}\n",
+                    session, data.username, data.addr, data.timestamp
+                )
+            })
             .collect::<Vec<String>>()
             .join("\n");
         write!(f, "[Config]\n{:?}\n[Clients]\n{}", self.config, client_data)
@@ -47,29 +197,100 @@ impl Debug for AppState {
 
 pub struct ClientData {
     pub timestamp: u64,
+    // the address the client performed the key exchange from, only used for logging
+    pub addr: IpAddr,
+    // the account the client authenticated as, and what it is allowed to do
+    pub username: String,
+    pub role: Role,
     pub cipher: Aes256GcmSiv,
+    // the confirmation the client has to send back to prove it knows the password
+    confirmation: Confirmation,
     authenticated: bool,
+    // the sequence numbers of the actions the client sent
+    sequences: Sequences,
 }
 
 impl ClientData {
-    pub fn new(key: &[u8; 32]) -> Self {
+    pub fn new(addr: IpAddr, username: String, role: Role, keys: &SessionKeys) -> Self {
         Self {
             timestamp: get_timestamp(),
-            // unwrap because our key is guaranteed to be 32 bytes long
-            cipher: Aes256GcmSiv::new_from_slice(key).unwrap(),
+            addr,
+            username,
+            role,
+            cipher: keys.cipher(),
+            confirmation: keys.client_confirmation(),
             authenticated: false,
+            sequences: Sequences::default(),
         }
     }
     pub fn is_authenticated(&self) -> bool {
         self.authenticated
     }
-    pub fn authenticate(&mut self) {
-        self.authenticated = true;
+    /// authenticates the client only if its confirmation matches ours
+    pub fn authenticate(&mut self, confirmation: &Confirmation) -> bool {
+        self.authenticated = self.confirmation == *confirmation;
+        self.authenticated
     }
 }
 
 impl Debug for ClientData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Client :\ncreation timestamp = {}", self.timestamp)
+        write!(
+            f,
+            "Client :\nusername = {}\naddress = {}\ncreation timestamp = {}",
+            self.username, self.addr, self.timestamp
+        )
+    }
+}
+
+/// the highest sequence number a session sent an action with, and which of the `SEQUENCE_WINDOW` ones below it were sent
+#[derive(Default)]
+struct Sequences {
+    highest: u64,
+    // bit n is set if `highest - n` was sent
+    sent: u64,
+}
+
+impl Sequences {
+    /// returns whether the sequence number was never sent before, and records it
+    fn accept(&mut self, sequence: u64) -> bool {
+        if sequence > self.highest {
+            let shift = sequence - self.highest;
+            self.sent = if shift < SEQUENCE_WINDOW {
+                self.sent << shift
+            } else {
+                0
+            } | 1;
+            self.highest = sequence;
+            return true;
+        }
+        let offset = self.highest - sequence;
+        if offset >= SEQUENCE_WINDOW || self.sent & (1 << offset) != 0 {
+            return false;
+        }
+        self.sent |= 1 << offset;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sequences;
+
+    #[test]
+    fn sequences() {
+        let mut sequences = Sequences::default();
+        assert!(sequences.accept(0));
+        assert!(!sequences.accept(0));
+        assert!(sequences.accept(2));
+        // sent concurrently, arrives late
+        assert!(sequences.accept(1));
+        assert!(!sequences.accept(1) && !sequences.accept(2));
+        assert!(sequences.accept(100));
+        assert!(sequences.accept(37));
+        assert!(!sequences.accept(36));
+        assert!(!sequences.accept(2));
+        assert!(sequences.accept(1000));
+        assert!(!sequences.accept(100));
     }
 }
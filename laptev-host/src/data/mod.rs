@@ -1,2 +1,4 @@
-pub mod external;
+pub mod index;
 pub mod internal;
+pub mod pins;
+pub mod storage;
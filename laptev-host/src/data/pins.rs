@@ -0,0 +1,74 @@
+use anyhow::Context;
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{io::AsyncWriteExt, sync::Mutex};
+
+const PINS_FILE: &str = "laptev.pins";
+
+/// the events pinned by clients, kept whatever their age and the storage budget,
+/// stored in laptev.pins (next to the configuration) as a JSON list of timestamps
+#[derive(Debug, Clone, Default)]
+pub struct Pins {
+    path: PathBuf,
+    timestamps: BTreeSet<u64>,
+    // shared by every copy, so that the pins are changed and saved one update at a time
+    pub(super) updating: Arc<Mutex<()>>,
+}
+
+impl Pins {
+    /// loads laptev.pins from the given directory, there are no pins if there is none,
+    /// one that exists but fails to load is never overwritten, the host refuses to start instead
+    pub async fn load(directory: &Path) -> anyhow::Result<Self> {
+        let path = directory.join(PINS_FILE);
+        let timestamps = match tokio::fs::read(&path).await {
+            Ok(buffer) => serde_json::from_slice(&buffer)
+                .with_context(|| format!("{} is malformed", path.display()))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to read {}", path.display()))
+            }
+        };
+        Ok(Self {
+            path,
+            timestamps,
+            updating: Arc::default(),
+        })
+    }
+
+    /// written to a temporary file first, a crash halfway through never loses every pin
+    pub async fn save(&self) -> anyhow::Result<()> {
+        let mut temporary_path = self.path.clone().into_os_string();
+        temporary_path.push(".tmp");
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&temporary_path)
+            .await?;
+        file.write_all(&serde_json::to_vec(&self.timestamps)?)
+            .await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&temporary_path, &self.path).await?;
+        Ok(())
+    }
+
+    pub fn contains(&self, timestamp: u64) -> bool {
+        self.timestamps.contains(&timestamp)
+    }
+
+    pub fn timestamps(&self) -> impl Iterator<Item = u64> + '_ {
+        self.timestamps.iter().copied()
+    }
+
+    /// pins or unpins an event, in memory only, returns whether anything changed
+    pub fn set(&mut self, timestamp: u64, pinned: bool) -> bool {
+        if pinned {
+            self.timestamps.insert(timestamp)
+        } else {
+            self.timestamps.remove(&timestamp)
+        }
+    }
+}
@@ -0,0 +1,457 @@
+//! where the files of the events are stored, every access to them goes through here,
+//! thumbnails and clips share the data directory unless they are given directories of their own,
+//! e.g. clips on a USB drive and thumbnails on the SD card
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::fs::File;
+
+use crate::{config::Config, mp4};
+
+/// where the events are stored, unless told otherwise by the configuration or with --data-dir
+pub const DATA_DIRECTORY: &str = "./data";
+/// motioncapture.py records clips under this suffix, then renames them once they are complete
+pub const RECORDING_SUFFIX: &str = ".recording";
+/// deleted events are moved to this subdirectory of the directory of each kind, on the same disk,
+/// until they are restored or purged
+const TRASH_DIRECTORY: &str = "trash";
+/// the files the reconciliation takes out of the way are moved to this subdirectory of their directory,
+/// they stay there until someone looks into them
+const QUARANTINE_DIRECTORY: &str = "quarantine";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    // `<timestamp>.jpg`
+    Thumbnail,
+    // `<timestamp>.mp4`
+    Clip,
+    // `<timestamp>.recording.mp4`
+    Recording,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 3] = [Kind::Thumbnail, Kind::Clip, Kind::Recording];
+
+    fn file_name(&self, timestamp: u64) -> String {
+        match self {
+            Kind::Thumbnail => format!("{}.jpg", timestamp),
+            Kind::Clip => format!("{}.mp4", timestamp),
+            Kind::Recording => format!("{}{}.mp4", timestamp, RECORDING_SUFFIX),
+        }
+    }
+}
+
+/// the state of one file of an event, as found on disk
+pub struct Probe {
+    pub timestamp: u64,
+    pub kind: Kind,
+    // None if the file does not exist (anymore)
+    pub size: Option<u64>,
+    pub duration: Option<u64>,
+}
+
+/// an event in the trash
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Trashed {
+    pub thumbnail_size: Option<u64>,
+    pub clip_size: Option<u64>,
+    // the timestamp it was deleted at
+    pub deleted_at: u64,
+}
+
+/// a disk holding some of the directories, and what is left of it
+#[derive(Debug, Clone)]
+pub struct Disk {
+    // the first directory found on it
+    pub directory: PathBuf,
+    // the kinds of files stored on it, recordings are wherever the clips are
+    pub kinds: Vec<Kind>,
+    // in bytes
+    pub total: u64,
+    pub available: u64,
+}
+
+impl Disk {
+    /// the share of the disk that is free, in percent
+    pub fn free_space(&self) -> f64 {
+        match self.total {
+            0 => 100.0,
+            total => self.available as f64 * 100.0 / total as f64,
+        }
+    }
+
+    /// how many bytes have to be freed for the given share of the disk (in percent) to be free
+    pub fn missing(&self, free_space: u8) -> u64 {
+        u64::try_from(self.total as u128 * free_space as u128 / 100)
+            .unwrap_or(u64::MAX)
+            .saturating_sub(self.available)
+    }
+
+    /// how many bytes the files of an event take on this disk
+    pub fn share(&self, thumbnail_size: Option<u64>, clip_size: Option<u64>) -> u64 {
+        self.kinds
+            .iter()
+            .map(|kind| match kind {
+                Kind::Thumbnail => thumbnail_size.unwrap_or(0),
+                Kind::Clip | Kind::Recording => clip_size.unwrap_or(0),
+            })
+            .sum()
+    }
+}
+
+/// tells apart the disks directories are on
+#[cfg(unix)]
+fn device(directory: &Path) -> std::io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(std::fs::metadata(directory)?.dev())
+}
+
+/// without device ids, directories on disks of the same size are taken to be on the same disk
+#[cfg(not(unix))]
+fn device(directory: &Path) -> std::io::Result<u64> {
+    fs2::total_space(directory)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Storage {
+    thumbnails: PathBuf,
+    clips: PathBuf,
+}
+
+impl Storage {
+    /// the data directory given on the command line, if any, takes precedence over the one of the configuration
+    pub fn new(config: &Config, data_directory: Option<&Path>) -> Self {
+        let data_directory = data_directory.unwrap_or(&config.data_directory);
+        Self {
+            thumbnails: config
+                .thumbnail_directory
+                .clone()
+                .unwrap_or_else(|| data_directory.to_path_buf()),
+            clips: config
+                .clip_directory
+                .clone()
+                .unwrap_or_else(|| data_directory.to_path_buf()),
+        }
+    }
+
+    pub fn directory(&self, kind: Kind) -> &Path {
+        match kind {
+            Kind::Thumbnail => &self.thumbnails,
+            Kind::Clip | Kind::Recording => &self.clips,
+        }
+    }
+
+    /// every directory holding files of events, each only once
+    pub fn directories(&self) -> Vec<&Path> {
+        if self.thumbnails == self.clips {
+            vec![&self.thumbnails]
+        } else {
+            vec![&self.thumbnails, &self.clips]
+        }
+    }
+
+    pub fn path(&self, timestamp: u64, kind: Kind) -> PathBuf {
+        self.directory(kind).join(kind.file_name(timestamp))
+    }
+
+    fn trash_directory(&self, kind: Kind) -> PathBuf {
+        self.directory(kind).join(TRASH_DIRECTORY)
+    }
+
+    fn trash_path(&self, timestamp: u64, kind: Kind) -> PathBuf {
+        self.trash_directory(kind).join(kind.file_name(timestamp))
+    }
+
+    /// whether the path is one of the subdirectories laptev-host keeps in the directories, the trash or the quarantine
+    pub fn is_internal(&self, path: &Path) -> bool {
+        self.directories().into_iter().any(|directory| {
+            path == directory.join(TRASH_DIRECTORY) || path == directory.join(QUARANTINE_DIRECTORY)
+        })
+    }
+
+    /// only `<timestamp>.jpg`, `<timestamp>.mp4` and `<timestamp>.recording.mp4` files are part of an event,
+    /// and only when they are in the directory their kind is stored in
+    pub fn parse(&self, path: &Path) -> Option<(u64, Kind)> {
+        Self::parse_file_name(path).filter(|(_, kind)| path.parent() == Some(self.directory(*kind)))
+    }
+
+    fn parse_file_name(path: &Path) -> Option<(u64, Kind)> {
+        let stem = path.file_stem()?.to_str()?;
+        let (stem, kind) = match (
+            path.extension()?.to_str()?,
+            stem.strip_suffix(RECORDING_SUFFIX),
+        ) {
+            ("jpg", None) => (stem, Kind::Thumbnail),
+            ("mp4", None) => (stem, Kind::Clip),
+            ("mp4", Some(stem)) => (stem, Kind::Recording),
+            _ => return None,
+        };
+        Some((stem.parse::<u64>().ok()?, kind))
+    }
+
+    pub async fn probe(&self, path: &Path) -> Option<Probe> {
+        let (timestamp, kind) = self.parse(path)?;
+        let size = tokio::fs::metadata(path)
+            .await
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len());
+        let duration = match (kind, size) {
+            (Kind::Clip, Some(..)) => mp4::duration(path).await,
+            _ => None,
+        };
+        Some(Probe {
+            timestamp,
+            kind,
+            size,
+            duration,
+        })
+    }
+
+    /// every file in the directories, whether it is part of an event or not
+    pub async fn files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = Vec::new();
+        for directory in self.directories() {
+            if let Ok(mut read_dir) = tokio::fs::read_dir(directory).await {
+                while let Ok(Some(entry)) = read_dir.next_entry().await {
+                    files.push(entry.path());
+                }
+            }
+        }
+        files
+    }
+
+    /// the disks the directories are on, usually one, or two when the clips are kept on a larger drive
+    pub fn disks(&self) -> std::io::Result<Vec<Disk>> {
+        let mut disks: Vec<(u64, Disk)> = Vec::new();
+        for kind in [Kind::Thumbnail, Kind::Clip] {
+            let directory = self.directory(kind);
+            let device = device(directory)?;
+            match disks.iter_mut().find(|(other, _)| *other == device) {
+                Some((_, disk)) => disk.kinds.push(kind),
+                None => disks.push((
+                    device,
+                    Disk {
+                        directory: directory.to_path_buf(),
+                        kinds: vec![kind],
+                        total: fs2::total_space(directory)?,
+                        available: fs2::available_space(directory)?,
+                    },
+                )),
+            }
+        }
+        Ok(disks.into_iter().map(|(_, disk)| disk).collect())
+    }
+
+    pub async fn create_directories(&self) -> std::io::Result<()> {
+        for directory in self.directories() {
+            tokio::fs::create_dir_all(directory.join(TRASH_DIRECTORY)).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn read(&self, timestamp: u64, kind: Kind) -> std::io::Result<Vec<u8>> {
+        tokio::fs::read(self.path(timestamp, kind)).await
+    }
+
+    pub async fn open(&self, timestamp: u64, kind: Kind) -> std::io::Result<File> {
+        File::open(self.path(timestamp, kind)).await
+    }
+
+    pub async fn remove(&self, timestamp: u64, kind: Kind) -> std::io::Result<()> {
+        tokio::fs::remove_file(self.path(timestamp, kind)).await
+    }
+
+    /// removes every file of an event, those that are already gone are ignored
+    pub async fn remove_event(&self, timestamp: u64) {
+        // also removes the clips left behind by an interrupted recording
+        for kind in Kind::ALL {
+            match self.remove(timestamp, kind).await {
+                Ok(()) => tracing::info!("removed {}", self.path(timestamp, kind).display()),
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => (),
+                Err(error) => tracing::warn!("failed to remove an old file\n{}", error),
+            }
+        }
+    }
+
+    /// moves a file of one of the directories to its quarantine, returns where it now is,
+    /// a file already quarantined under the same name is never replaced
+    pub async fn quarantine(&self, path: &Path) -> std::io::Result<PathBuf> {
+        let (Some(directory), Some(file_name)) = (path.parent(), path.file_name()) else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} is not in a directory", path.display()),
+            ));
+        };
+        let quarantine = directory.join(QUARANTINE_DIRECTORY);
+        tokio::fs::create_dir_all(&quarantine).await?;
+        let destination = quarantine.join(file_name);
+        if tokio::fs::try_exists(&destination).await? {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} already exists", destination.display()),
+            ));
+        }
+        tokio::fs::rename(path, &destination).await?;
+        Ok(destination)
+    }
+
+    /// when the file was last written to, None if it does not exist
+    pub async fn modified(
+        &self,
+        timestamp: u64,
+        kind: Kind,
+    ) -> std::io::Result<Option<SystemTime>> {
+        match tokio::fs::metadata(self.path(timestamp, kind)).await {
+            Ok(metadata) => metadata.modified().map(Some),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// moves the files of an event to the trash, all of them or none, returns the kinds that were moved,
+    /// the time they were deleted at is kept as their modification time,
+    /// a recording is moved as well, it is up to the caller to check that it is not still being written to
+    pub async fn trash(&self, timestamp: u64) -> std::io::Result<Vec<Kind>> {
+        let moved = self
+            .move_files(timestamp, |kind| {
+                (self.path(timestamp, kind), self.trash_path(timestamp, kind))
+            })
+            .await?;
+        for kind in moved.iter() {
+            let trash_path = self.trash_path(timestamp, *kind);
+            if let Err(error) = mark_deleted(&trash_path).await {
+                // the file would only be purged earlier than it should
+                tracing::warn!(
+                    "failed to record when {} was deleted\n{}",
+                    trash_path.display(),
+                    error
+                );
+            }
+        }
+        Ok(moved)
+    }
+
+    /// moves the files of an event back out of the trash, all of them or none, returns the kinds that were restored,
+    /// fails without moving anything if one of them would replace a file that is there again
+    pub async fn restore(&self, timestamp: u64) -> std::io::Result<Vec<Kind>> {
+        for kind in Kind::ALL {
+            if tokio::fs::try_exists(self.trash_path(timestamp, kind)).await?
+                && tokio::fs::try_exists(self.path(timestamp, kind)).await?
+            {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{} already exists", self.path(timestamp, kind).display()),
+                ));
+            }
+        }
+        self.move_files(timestamp, |kind| {
+            (self.trash_path(timestamp, kind), self.path(timestamp, kind))
+        })
+        .await
+    }
+
+    /// moves the files of an event that exist from one path to the other, returns the kinds that were moved,
+    /// if one of them cannot be moved, those already moved are put back so that the event is never left in pieces
+    async fn move_files(
+        &self,
+        timestamp: u64,
+        paths: impl Fn(Kind) -> (PathBuf, PathBuf),
+    ) -> std::io::Result<Vec<Kind>> {
+        let mut moved: Vec<Kind> = Vec::new();
+        for kind in Kind::ALL {
+            let (from, to) = paths(kind);
+            match tokio::fs::rename(&from, &to).await {
+                Ok(()) => moved.push(kind),
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => (),
+                Err(error) => {
+                    for kind in moved.into_iter() {
+                        let (from, to) = paths(kind);
+                        if let Err(error) = tokio::fs::rename(&to, &from).await {
+                            tracing::error!(
+                                "failed to move {} back, event {} is incomplete\n{}",
+                                to.display(),
+                                timestamp,
+                                error
+                            );
+                        }
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        Ok(moved)
+    }
+
+    /// the events in the trash, by timestamp
+    pub async fn trashed(&self) -> BTreeMap<u64, Trashed> {
+        let mut trashed: BTreeMap<u64, Trashed> = BTreeMap::new();
+        for directory in self.directories() {
+            let Ok(mut read_dir) = tokio::fs::read_dir(directory.join(TRASH_DIRECTORY)).await
+            else {
+                continue;
+            };
+            while let Ok(Some(entry)) = read_dir.next_entry().await {
+                let path = entry.path();
+                let Some((timestamp, kind)) = Self::parse_file_name(&path)
+                    .filter(|(_, kind)| path.parent() == Some(&self.trash_directory(*kind)))
+                else {
+                    continue;
+                };
+                let Some(metadata) = entry
+                    .metadata()
+                    .await
+                    .ok()
+                    .filter(|metadata| metadata.is_file())
+                else {
+                    continue;
+                };
+                let event = trashed.entry(timestamp).or_default();
+                match kind {
+                    Kind::Thumbnail => event.thumbnail_size = Some(metadata.len()),
+                    // an interrupted recording counts as the clip, an event rarely has both
+                    _ => event.clip_size = Some(event.clip_size.unwrap_or(0) + metadata.len()),
+                }
+                let deleted_at = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |deleted_at| deleted_at.as_secs());
+                event.deleted_at = event.deleted_at.max(deleted_at);
+            }
+        }
+        trashed
+    }
+
+    /// removes the files of an event from the trash for good, those that are already gone are ignored,
+    /// returns the kinds that were removed
+    pub async fn purge(&self, timestamp: u64) -> Vec<Kind> {
+        let mut purged: Vec<Kind> = Vec::new();
+        for kind in Kind::ALL {
+            let trash_path = self.trash_path(timestamp, kind);
+            match tokio::fs::remove_file(&trash_path).await {
+                Ok(()) => {
+                    tracing::info!("purged {}", trash_path.display());
+                    purged.push(kind);
+                }
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => (),
+                Err(error) => tracing::warn!("failed to purge a deleted file\n{}", error),
+            }
+        }
+        purged
+    }
+}
+
+/// sets the modification time of a file that was just moved to the trash to now
+async fn mark_deleted(path: &Path) -> std::io::Result<()> {
+    let file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .await?
+        .into_std()
+        .await;
+    tokio::task::spawn_blocking(move || file.set_modified(SystemTime::now())).await?
+}
@@ -11,6 +11,12 @@ pub type Result<T> = core::result::Result<T, Error>;
 pub enum Error {
     HandshakeFailed,
     NotAuthenticated,
+    NotAuthorized,
+    TooManyRequests,
+    InvalidRange,
+    InvalidAction,
+    NotFound,
+    Recording,
     Internal,
 }
 
@@ -27,6 +33,16 @@ impl std::error::Error for Error {
                 "could not establish a secure and trusted connection with the client"
             }
             Self::NotAuthenticated => "not an authenticated client",
+            Self::NotAuthorized => "the client's role does not allow this request",
+            Self::TooManyRequests => {
+                "too many handshakes, the client has to wait before trying again"
+            }
+            Self::InvalidRange => "the requested offset is not a chunk boundary within the clip",
+            Self::InvalidAction => {
+                "the body of the request is not its action sealed with the session's cipher, or was already sent"
+            }
+            Self::NotFound => "no such entry",
+            Self::Recording => "the entry is still being recorded",
             Self::Internal => "internal server error",
         }
     }
@@ -37,6 +53,12 @@ impl IntoResponse for Error {
         match self {
             Self::HandshakeFailed => StatusCode::FORBIDDEN.into_response(),
             Self::NotAuthenticated => StatusCode::FORBIDDEN.into_response(),
+            Self::NotAuthorized => StatusCode::FORBIDDEN.into_response(),
+            Self::TooManyRequests => StatusCode::TOO_MANY_REQUESTS.into_response(),
+            Self::InvalidRange => StatusCode::RANGE_NOT_SATISFIABLE.into_response(),
+            Self::InvalidAction => StatusCode::BAD_REQUEST.into_response(),
+            Self::NotFound => StatusCode::NOT_FOUND.into_response(),
+            Self::Recording => StatusCode::CONFLICT.into_response(),
             Self::Internal => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
         }
     }
@@ -0,0 +1,92 @@
+use anyhow::Context;
+use laptev_protocol::identity::{fingerprint, SigningKey, VerifyingKey};
+use rand::SeedableRng;
+use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// the file the identity is stored in, next to the configuration
+const IDENTITY_FILE: &str = "laptev.identity";
+
+/// the host's long-term Ed25519 identity, stored next to laptev.config
+pub struct Identity(SigningKey);
+
+impl Identity {
+    /// loads laptev.identity, or generates and saves one if there is none,
+    /// one that exists but fails to load is never replaced, clients pinned it, the host refuses to start instead
+    pub async fn new(directory: &Path) -> anyhow::Result<Self> {
+        let path = directory.join(IDENTITY_FILE);
+        let identity = match Self::load(&path).await {
+            Ok(identity) => {
+                tracing::info!("identity loaded from {}", path.display());
+                identity
+            }
+            Err(error)
+                if error
+                    .downcast_ref::<std::io::Error>()
+                    .is_some_and(|error| error.kind() == std::io::ErrorKind::NotFound) =>
+            {
+                tracing::warn!("no identity found, generating {}", path.display());
+                let identity = Self::generate();
+                // a new identity on every launch would be as bad as a replaced one
+                identity.save(&path).await.with_context(|| {
+                    format!(
+                        "failed to save the generated identity to {}",
+                        path.display()
+                    )
+                })?;
+                identity
+            }
+            Err(error) => return Err(error.context(format!("failed to load {}", path.display()))),
+        };
+        tracing::info!(
+            "identity fingerprint : {}",
+            fingerprint(&identity.verifying_key())
+        );
+        Ok(identity)
+    }
+
+    pub fn signing_key(&self) -> &SigningKey {
+        &self.0
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        self.0.verifying_key()
+    }
+
+    async fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut options = tokio::fs::OpenOptions::new();
+        // never truncates an identity, even one that could not be loaded
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        options
+            .open(path)
+            .await?
+            .write_all(self.0.as_bytes())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn load(path: &Path) -> anyhow::Result<Self> {
+        let mut buffer: Vec<u8> = Vec::with_capacity(32);
+        tokio::fs::OpenOptions::new()
+            .create(false)
+            .read(true)
+            .open(path)
+            .await?
+            .read_to_end(&mut buffer)
+            .await?;
+        Ok(Self(SigningKey::from_bytes(
+            buffer
+                .as_slice()
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("{} is not 32 bytes long", path.display()))?,
+        )))
+    }
+
+    fn generate() -> Self {
+        Self(SigningKey::generate(&mut rand::rngs::StdRng::from_entropy()))
+    }
+}
@@ -0,0 +1,82 @@
+//! the log of the host when it is given --log-file, as launch.sh does, capped in size so that it never fills the SD card,
+//! the output of the capture process ends up in it as well
+
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// once the log reaches this size (in bytes), it is moved to `<path>.1` (replacing the previous one) and a new one is started
+const MAX_LOG_SIZE: u64 = 8_000_000;
+
+pub struct LogFile {
+    path: PathBuf,
+    // the file being written to and its size
+    file: Mutex<(File, u64)>,
+}
+
+impl LogFile {
+    /// appends to the log if it already exists
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new((file, size)),
+        })
+    }
+
+    fn rotate(&self) -> std::io::Result<File> {
+        let mut rotated_path = self.path.clone().into_os_string();
+        rotated_path.push(".1");
+        std::fs::rename(&self.path, rotated_path)?;
+        std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)
+    }
+}
+
+impl Write for &LogFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // a poisoned lock only means another thread panicked halfway through a line
+        let mut guard = self
+            .file
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (file, size) = &mut *guard;
+        if *size > 0 && *size + buf.len() as u64 > MAX_LOG_SIZE {
+            // keeps writing to the same file rather than losing lines if it cannot be rotated
+            if let Ok(rotated) = self.rotate() {
+                *file = rotated;
+                *size = 0;
+            }
+        }
+        file.write_all(buf)?;
+        *size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .0
+            .flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogFile {
+    type Writer = &'a LogFile;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self
+    }
+}
@@ -1,29 +1,92 @@
 use axum::Router;
-use std::{net::SocketAddr, sync::Arc};
+use clap::Parser;
+use std::{net::SocketAddr, path::Path, sync::Arc};
 use tokio::sync::RwLock;
 
+mod audit;
+mod cli;
+use cli::{Cli, Command};
 mod config;
 mod data;
 use data::internal::{AppState, SharedState};
 mod error;
+mod identity;
+mod logfile;
+use logfile::LogFile;
+mod mp4;
+mod quota;
+mod reconcile;
+mod supervisor;
+mod throttle;
+mod tls;
 mod utils;
 mod web;
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .compact()
-        .init();
+    let cli = Cli::parse();
+    match cli.command {
+        None | Some(Command::Serve) => {
+            let subscriber = tracing_subscriber::fmt()
+                .with_max_level(tracing::Level::INFO)
+                .compact();
+            match cli.log_file.as_deref().map(LogFile::open) {
+                None => subscriber.init(),
+                Some(Ok(log_file)) => subscriber.with_writer(log_file).with_ansi(false).init(),
+                Some(Err(error)) => {
+                    eprintln!("failed to open the log file\n{}", error);
+                    std::process::exit(1);
+                }
+            }
+            serve(&cli.config, cli.data_dir.as_deref()).await;
+        }
+        Some(command) => {
+            // only warnings are logged, the output of the command is what matters
+            tracing_subscriber::fmt()
+                .with_max_level(tracing::Level::WARN)
+                .with_writer(std::io::stderr)
+                .compact()
+                .init();
+            if let Err(error) = command.run(&cli.config, cli.data_dir.as_deref()).await {
+                eprintln!("{:#}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+}
 
-    let shared_state: SharedState = Arc::new(RwLock::new(AppState::new().await));
+async fn serve(config_path: &Path, data_directory: Option<&Path>) {
+    let app_state = match AppState::new(config_path, data_directory).await {
+        Ok(app_state) => app_state,
+        Err(error) => {
+            tracing::error!("{:#}", error);
+            std::process::exit(1);
+        }
+    };
+    let shared_state: SharedState = Arc::new(RwLock::new(app_state));
     let config = shared_state.read().await.config.clone();
 
-    let file_expiration_time = config.file_expiration_time;
+    // e.g. the data directory cannot be created or the inotify watch limit is reached
+    if let Err(error) = data::index::watch(shared_state.clone()).await {
+        tracing::error!("failed to watch the data directories\n{}", error);
+        std::process::exit(1);
+    }
+    reconcile::on_launch(shared_state.clone());
+    supervisor::supervise(shared_state.clone(), config.capture_command.clone());
+
+    if let Err(error) = config::watch(shared_state.clone(), config_path) {
+        tracing::error!("failed to watch {}\n{}", config_path.display(), error);
+        std::process::exit(1);
+    }
+    quota::watch(shared_state.clone());
+
+    let cleanup_state = shared_state.clone();
     tokio::spawn(async move {
         loop {
-            // removes any entry older than 3 days
-            utils::clean_older_than(file_expiration_time).await;
+            // removes any entry older than file_expiration_time (3 days by default)
+            utils::clean_older_than(&cleanup_state).await;
+            // and purges the events deleted more than trash_expiration_time ago (1 day by default)
+            utils::empty_trash(&cleanup_state).await;
             tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
         }
     });
@@ -33,15 +96,33 @@ async fn main() {
         .merge(
             crate::web::handshake::routes_handshake(shared_state.clone())
                 .merge(crate::web::handler::routes_handler(shared_state.clone())),
-        );
+        )
+        .merge(crate::web::admin::routes_admin(shared_state.clone()));
 
     let bindaddr: SocketAddr = SocketAddr::from(([0, 0, 0, 0], config.port));
     tracing::info!("binding to : {}", bindaddr);
-    let listener = tokio::net::TcpListener::bind(bindaddr).await.unwrap();
-    axum::serve(
-        listener,
-        router.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .await
-    .unwrap();
+    if config.tls {
+        let rustls_config = match tls::Certificate::new(config.directory())
+            .await
+            .and_then(|certificate| certificate.rustls_config())
+        {
+            Ok(rustls_config) => rustls_config,
+            Err(error) => {
+                tracing::error!("{:#}", error);
+                std::process::exit(1);
+            }
+        };
+        axum_server::bind_rustls(bindaddr, rustls_config)
+            .serve(router.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+            .unwrap();
+    } else {
+        let listener = tokio::net::TcpListener::bind(bindaddr).await.unwrap();
+        axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
+    }
 }
@@ -10,6 +10,19 @@ from picamera2.encoders import H264Encoder, Quality
 from picamera2.outputs import FfmpegOutput
 from PIL import Image
 from time import time, sleep
+from os import environ, path, rename
+
+# laptev-host tells us where it looks for thumbnails and clips when it runs this script
+thumbnail_directory = environ.get("LAPTEV_THUMBNAIL_DIRECTORY", "data")
+clip_directory = environ.get("LAPTEV_CLIP_DIRECTORY", "data")
+
+# clips are recorded under a temporary name, laptev-host only serves them once they are renamed
+def recording_path(timestamp):
+    return path.join(clip_directory, f"{timestamp}.recording.mp4")
+
+def stop_encoding(timestamp):
+    picam2.stop_encoder()
+    rename(recording_path(timestamp), path.join(clip_directory, f"{timestamp}.mp4"))
 
 lq_size = (576, 324)
 hq_size = (1536, 864)
@@ -96,9 +109,11 @@ while True:
 
                     thumbnail = Image.fromarray(picam2.capture_array("main"), "RGB")
                     thumbnail.thumbnail((512, 288))
-                    thumbnail.save(f"data/{timestamp}.jpg")
+                    thumbnail_path = path.join(thumbnail_directory, f"{timestamp}.jpg")
+                    thumbnail.save(f"{thumbnail_path}.tmp", format="JPEG")
+                    rename(f"{thumbnail_path}.tmp", thumbnail_path)
 
-                    encoder.output = FfmpegOutput(f"data/{timestamp}.mp4")
+                    encoder.output = FfmpegOutput(recording_path(timestamp))
                     picam2.start_encoder(encoder=picam2.encoder, output=encoder.output, quality=Quality.LOW)
                     encoding = True
                     motion_count = 0
@@ -108,14 +123,14 @@ while True:
                 if encoding: 
                     threshold_update_guard += 20
                     print("10 seconds reached, stopped encoding")
-                    picam2.stop_encoder()
+                    stop_encoding(timestamp)
                     encoding = False
                     motion_count = 0
         else:
             timediff = cur_time - ltime
             if encoding and timediff > 2.25:
                 print(f"stopped encoding, {timediff}")
-                picam2.stop_encoder()
+                stop_encoding(timestamp)
                 encoding = False
                 motion_count = 0
     prev = cur
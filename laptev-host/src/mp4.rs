@@ -0,0 +1,75 @@
+use std::{io::SeekFrom, path::Path};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// the duration of a clip in milliseconds, read from its movie header box (moov > mvhd),
+/// only the box headers are read, not the rest of the file
+pub async fn duration(path: &Path) -> Option<u64> {
+    let mut file = tokio::fs::File::open(path).await.ok()?;
+    let length = file.metadata().await.ok()?.len();
+    let (moov_start, moov_end) = find_box(&mut file, 0, length, b"moov").await?;
+    let (mvhd_start, _) = find_box(&mut file, moov_start, moov_end, b"mvhd").await?;
+
+    file.seek(SeekFrom::Start(mvhd_start)).await.ok()?;
+    // version (1 byte), flags (3 bytes), then the creation and modification times,
+    // the timescale and the duration, all 32 bits long in version 0, times and duration 64 bits long in version 1
+    let version = file.read_u32().await.ok()? >> 24;
+    let (timescale, duration) = match version {
+        0 => {
+            file.seek(SeekFrom::Current(8)).await.ok()?;
+            (
+                file.read_u32().await.ok()?,
+                file.read_u32().await.ok()? as u64,
+            )
+        }
+        1 => {
+            file.seek(SeekFrom::Current(16)).await.ok()?;
+            (file.read_u32().await.ok()?, file.read_u64().await.ok()?)
+        }
+        _ => return None,
+    };
+    if timescale == 0 {
+        return None;
+    }
+    u64::try_from(duration as u128 * 1000 / timescale as u128).ok()
+}
+
+/// looks for a box of the given type between two positions of the file,
+/// returns where its content starts and ends
+async fn find_box(
+    file: &mut tokio::fs::File,
+    mut position: u64,
+    end: u64,
+    kind: &[u8; 4],
+) -> Option<(u64, u64)> {
+    while end.saturating_sub(position) >= 8 {
+        file.seek(SeekFrom::Start(position)).await.ok()?;
+        let mut size = file.read_u32().await.ok()? as u64;
+        let mut header: [u8; 4] = [0; 4];
+        file.read_exact(&mut header).await.ok()?;
+
+        let mut header_length: u64 = 8;
+        match size {
+            // the size does not fit in 32 bits, it follows the type
+            1 => {
+                size = file.read_u64().await.ok()?;
+                header_length = 16;
+            }
+            // the box extends to the end of its parent
+            0 => size = end - position,
+            _ => (),
+        }
+        if size < header_length {
+            return None;
+        }
+        // a corrupt size could overflow, or point past the parent
+        let end_of_box = position
+            .checked_add(size)
+            .filter(|end_of_box| *end_of_box <= end)?;
+
+        if &header == kind {
+            return Some((position + header_length, end_of_box));
+        }
+        position = end_of_box;
+    }
+    None
+}
@@ -0,0 +1,203 @@
+//! keeps the events within the storage budget of the configuration, on top of their age-based retention,
+//! the oldest events are evicted first, until the events fit in max_storage_size and min_free_space of each disk is free
+
+use std::{collections::BTreeSet, time::Duration};
+
+use crate::data::{index::Event, internal::SharedState};
+
+/// how often the budget is checked, a windy day can fill the disk in a few hours
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// checks the budget every CHECK_INTERVAL for as long as the host runs
+pub fn watch(state: SharedState) {
+    tokio::spawn(async move {
+        loop {
+            enforce(&state).await;
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+/// evicts the oldest events until the budget is met, pinned events and clips still being recorded are never evicted,
+/// once less than critical_free_space of a disk is free, events are evicted until twice that is free, whatever the budget,
+/// the trash is purged before any event is evicted to free some space
+///
+/// the space to free is worked out in bytes, disk by disk, counting only the files on the disk that is short,
+/// and nothing is removed from a disk the events and the trash could not free enough of, something else is filling it
+pub async fn enforce(state: &SharedState) {
+    let (storage, max_storage_size, min_free_space, critical_free_space, used, events) = {
+        let read_state = state.read().await;
+        let config = &read_state.config;
+        let used: u64 = read_state
+            .index
+            .events()
+            .map(|(_, event)| event.size())
+            .sum();
+        // oldest first
+        let events: Vec<(u64, Event)> = read_state
+            .index
+            .events()
+            .filter(|(timestamp, event)| {
+                !event.recording && !read_state.index.is_pinned(*timestamp)
+            })
+            .map(|(timestamp, event)| (timestamp, *event))
+            .collect();
+        (
+            read_state.storage.clone(),
+            config.max_storage_size,
+            config.min_free_space,
+            config.critical_free_space,
+            used,
+            events,
+        )
+    };
+
+    let mut evicted: BTreeSet<u64> = BTreeSet::new();
+    if let Some(max_storage_size) = max_storage_size {
+        let candidates = events
+            .iter()
+            .map(|(timestamp, event)| (*timestamp, event.size()));
+        evicted.extend(select(candidates, used.saturating_sub(max_storage_size)).0);
+    }
+
+    let disks = match storage.disks() {
+        Ok(disks) => disks,
+        Err(error) => {
+            tracing::warn!("failed to get the free space of the disk\n{}", error);
+            Vec::new()
+        }
+    };
+    for disk in disks.iter() {
+        let emergency = critical_free_space
+            .filter(|critical_free_space| disk.free_space() < *critical_free_space as f64);
+        if let Some(critical_free_space) = emergency {
+            tracing::error!(
+                "less than {}% of the disk holding {} is free, removing the oldest events until {}% is",
+                critical_free_space,
+                disk.directory.display(),
+                critical_free_space * 2
+            );
+        }
+        let Some(target_free_space) = min_free_space.max(emergency.map(|critical| critical * 2))
+        else {
+            continue;
+        };
+        let share = |timestamp: &u64| {
+            events
+                .iter()
+                .find(|(other, _)| other == timestamp)
+                .map_or(0, |(_, event)| {
+                    disk.share(event.thumbnail_size, event.clip_size)
+                })
+        };
+        // the events already evicted to stay within max_storage_size free some of it
+        let missing = disk
+            .missing(target_free_space)
+            .saturating_sub(evicted.iter().map(share).sum());
+        if missing == 0 {
+            continue;
+        }
+
+        // the trash takes space on the disk, but is not part of the budget of the events
+        let mut trashed: Vec<(u64, u64, u64)> = storage
+            .trashed()
+            .await
+            .into_iter()
+            .map(|(timestamp, trashed)| {
+                let size = disk.share(trashed.thumbnail_size, trashed.clip_size);
+                (trashed.deleted_at, timestamp, size)
+            })
+            .collect();
+        trashed.sort_unstable();
+        let trashed: Vec<(u64, u64)> = trashed
+            .into_iter()
+            .map(|(_, timestamp, size)| (timestamp, size))
+            .collect();
+        let candidates: Vec<(u64, u64)> = events
+            .iter()
+            .filter(|(timestamp, _)| !evicted.contains(timestamp))
+            .map(|(timestamp, event)| {
+                (
+                    *timestamp,
+                    disk.share(event.thumbnail_size, event.clip_size),
+                )
+            })
+            .collect();
+
+        // something else fills the disk, removing every event would not be enough, and would lose them for nothing
+        let available: u64 = trashed
+            .iter()
+            .chain(candidates.iter())
+            .map(|(_, size)| size)
+            .sum();
+        if available < missing {
+            tracing::error!(
+                "the disk holding {} is {} bytes short, more than the events and the trash take, none of them were removed",
+                disk.directory.display(),
+                missing
+            );
+            continue;
+        }
+
+        let (purged, freed) = select(trashed.into_iter(), missing);
+        for timestamp in purged.iter() {
+            storage.purge(*timestamp).await;
+        }
+        if !purged.is_empty() {
+            tracing::warn!("purged {} deleted events to free some space", purged.len());
+        }
+        evicted.extend(select(candidates.into_iter(), missing.saturating_sub(freed)).0);
+    }
+
+    for timestamp in evicted.iter() {
+        storage.remove_event(*timestamp).await;
+        state.write().await.index.remove(*timestamp);
+    }
+    if !evicted.is_empty() {
+        tracing::warn!(
+            "removed the {} oldest events to stay within the storage budget",
+            evicted.len()
+        );
+    }
+}
+
+/// picks the candidates (oldest first, along with the bytes evicting them frees) to evict for `missing` bytes to be freed,
+/// the oldest first, skipping those that free nothing, returns them along with the bytes they free,
+/// which falls short of `missing` when the candidates are not enough
+fn select(candidates: impl Iterator<Item = (u64, u64)>, missing: u64) -> (Vec<u64>, u64) {
+    let mut selected: Vec<u64> = Vec::new();
+    let mut freed: u64 = 0;
+    for (timestamp, size) in candidates {
+        if freed >= missing {
+            break;
+        }
+        if size > 0 {
+            selected.push(timestamp);
+            freed += size;
+        }
+    }
+    (selected, freed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::select;
+
+    #[test]
+    fn eviction() {
+        let candidates = [(10, 400), (20, 0), (30, 100), (40, 2000), (50, 300)];
+        let evict = |missing| select(candidates.into_iter(), missing);
+
+        // nothing is missing, nothing is evicted
+        assert_eq!(evict(0), (vec![], 0));
+        // the oldest events are evicted first, until what they free covers what is missing
+        assert_eq!(evict(1), (vec![10], 400));
+        assert_eq!(evict(400), (vec![10], 400));
+        // events that free nothing on the disk are skipped
+        assert_eq!(evict(401), (vec![10, 30], 500));
+        assert_eq!(evict(501), (vec![10, 30, 40], 2500));
+        // more is missing than the events take, what they free falls short, which enforce() checks for before evicting any
+        assert_eq!(evict(10_000), (vec![10, 30, 40, 50], 2800));
+        assert_eq!(evict(u64::MAX), (vec![10, 30, 40, 50], 2800));
+    }
+}
@@ -0,0 +1,376 @@
+//! checks that the data directories only hold complete events: every thumbnail has a clip and every clip a thumbnail,
+//! no file is empty and nothing else piles up in them, on launch and on demand (through `endpoint::RECONCILE`
+//! or `laptev-host reconcile`), what is found is reported and optionally quarantined or repaired
+
+use anyhow::Context;
+use laptev_protocol::admin::{Finding, Inconsistency, ReconcileAction, Resolution};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    data::{
+        internal::SharedState,
+        pins::Pins,
+        storage::{Kind, Storage},
+    },
+    mp4,
+};
+
+/// files written to this recently may belong to an event being captured, they are left alone
+pub const GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// the sizes of the files of an event, as found on disk
+#[derive(Default)]
+struct Files {
+    thumbnail: Option<u64>,
+    clip: Option<u64>,
+    recording: Option<u64>,
+    // one of them was written to within GRACE_PERIOD
+    busy: bool,
+}
+
+/// runs reconcile_on_launch once, without holding up the launch
+pub fn on_launch(state: SharedState) {
+    tokio::spawn(async move {
+        let (storage, pins, action) = {
+            let read_state = state.read().await;
+            (
+                read_state.storage.clone(),
+                read_state.index.pins().clone(),
+                read_state.config.reconcile_on_launch,
+            )
+        };
+        let findings = reconcile(&storage, &pins, action).await;
+        for finding in findings.iter() {
+            tracing::warn!(
+                "{} : {:?}, {:?}",
+                finding.path,
+                finding.inconsistency,
+                finding.resolution
+            );
+        }
+        if !findings.is_empty() {
+            tracing::warn!(
+                "found {} inconsistencies in the data directories",
+                findings.len()
+            );
+        }
+    });
+}
+
+/// finds the files that are not part of a complete event and acts on them,
+/// those of pinned events are only ever reported, someone chose to keep them as they are
+pub async fn reconcile(storage: &Storage, pins: &Pins, action: ReconcileAction) -> Vec<Finding> {
+    let now = SystemTime::now();
+    let mut events: BTreeMap<u64, Files> = BTreeMap::new();
+    let mut findings: Vec<Finding> = Vec::new();
+
+    for path in storage.files().await.into_iter() {
+        if storage.is_internal(&path) {
+            continue;
+        }
+        // the file may have been removed since
+        let Ok(metadata) = tokio::fs::symlink_metadata(&path).await else {
+            continue;
+        };
+        let busy = metadata.modified().map_or(true, |modified| {
+            now.duration_since(modified)
+                .map_or(true, |age| age < GRACE_PERIOD)
+        });
+        match storage.parse(&path) {
+            Some((timestamp, kind)) if metadata.is_file() => {
+                let files = events.entry(timestamp).or_default();
+                files.busy |= busy;
+                let size = Some(metadata.len());
+                match kind {
+                    Kind::Thumbnail => files.thumbnail = size,
+                    Kind::Clip => files.clip = size,
+                    Kind::Recording => files.recording = size,
+                }
+            }
+            // e.g. the temporary thumbnail motioncapture.py is writing
+            _ if busy => (),
+            _ => findings.push(Finding {
+                path: path.display().to_string(),
+                timestamp: None,
+                inconsistency: Inconsistency::UnknownFile,
+                resolution: Resolution::Reported,
+            }),
+        }
+    }
+
+    for (timestamp, files) in events.into_iter().filter(|(_, files)| !files.busy) {
+        let mut found = |kind: Kind, inconsistency: Inconsistency| {
+            findings.push(Finding {
+                path: storage.path(timestamp, kind).display().to_string(),
+                timestamp: Some(timestamp),
+                inconsistency,
+                resolution: Resolution::Reported,
+            })
+        };
+        let has_clip = files.clip.is_some_and(|size| size > 0) || files.recording.is_some();
+        match files.thumbnail {
+            Some(0) => found(Kind::Thumbnail, Inconsistency::EmptyThumbnail),
+            Some(..) if !has_clip => found(Kind::Thumbnail, Inconsistency::OrphanedThumbnail),
+            _ => (),
+        }
+        match files.clip {
+            Some(0) => found(Kind::Clip, Inconsistency::EmptyClip),
+            Some(..) if files.thumbnail.is_none() => found(Kind::Clip, Inconsistency::OrphanedClip),
+            _ => (),
+        }
+        // recordings are written to continuously, one left untouched for GRACE_PERIOD was interrupted
+        if files.recording.is_some() {
+            found(Kind::Recording, Inconsistency::StaleRecording);
+        }
+    }
+
+    if action != ReconcileAction::Report {
+        for finding in findings.iter_mut().filter(|finding| {
+            !finding
+                .timestamp
+                .is_some_and(|timestamp| pins.contains(timestamp))
+        }) {
+            finding.resolution = resolve(storage, finding, action).await;
+        }
+    }
+    findings
+}
+
+/// repairs the file if asked to and it can be, quarantines it otherwise
+async fn resolve(storage: &Storage, finding: &Finding, action: ReconcileAction) -> Resolution {
+    let path = PathBuf::from(&finding.path);
+    if let (ReconcileAction::Repair, Some(timestamp)) = (action, finding.timestamp) {
+        match repair(storage, &path, timestamp, finding.inconsistency).await {
+            Ok(true) => return Resolution::Repaired,
+            Ok(false) => (),
+            Err(error) => tracing::warn!("failed to repair {}\n{:#}", path.display(), error),
+        }
+    }
+    match storage.quarantine(&path).await {
+        Ok(..) => Resolution::Quarantined,
+        Err(error) => {
+            tracing::warn!("failed to quarantine {}\n{}", path.display(), error);
+            Resolution::Failed
+        }
+    }
+}
+
+/// returns whether the inconsistency is one that can be repaired
+async fn repair(
+    storage: &Storage,
+    path: &Path,
+    timestamp: u64,
+    inconsistency: Inconsistency,
+) -> anyhow::Result<bool> {
+    match inconsistency {
+        Inconsistency::OrphanedClip => {
+            generate_thumbnail(path, &storage.path(timestamp, Kind::Thumbnail)).await?
+        }
+        Inconsistency::EmptyThumbnail => {
+            generate_thumbnail(&storage.path(timestamp, Kind::Clip), path).await?
+        }
+        // there is nothing in it to keep
+        Inconsistency::EmptyClip => tokio::fs::remove_file(path).await?,
+        // completes the recording, as long as what was recorded can be played
+        Inconsistency::StaleRecording => {
+            let clip = storage.path(timestamp, Kind::Clip);
+            if mp4::duration(path).await.is_none() {
+                anyhow::bail!("the recording cannot be played");
+            }
+            if tokio::fs::try_exists(&clip).await? {
+                anyhow::bail!("{} already exists", clip.display());
+            }
+            tokio::fs::rename(path, &clip).await?;
+        }
+        Inconsistency::OrphanedThumbnail | Inconsistency::UnknownFile => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// takes the first frame of the clip, scaled down like motioncapture.py does, with the ffmpeg the capture relies on
+async fn generate_thumbnail(clip: &Path, thumbnail: &Path) -> anyhow::Result<()> {
+    // written under a temporary name first, the watcher would otherwise index a partial thumbnail
+    let temporary_path = thumbnail.with_extension("jpg.tmp");
+    let output = tokio::process::Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-i"])
+        .arg(clip)
+        .args([
+            "-frames:v",
+            "1",
+            "-vf",
+            "scale=512:288:force_original_aspect_ratio=decrease",
+            "-f",
+            "mjpeg",
+        ])
+        .arg(&temporary_path)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("failed to run ffmpeg")?;
+    if !output.status.success() {
+        let _ = tokio::fs::remove_file(&temporary_path).await;
+        anyhow::bail!(
+            "ffmpeg failed to extract a thumbnail\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    tokio::fs::rename(&temporary_path, thumbnail).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::reconcile;
+    use crate::{
+        config::Config,
+        data::{pins::Pins, storage::Storage},
+    };
+    use laptev_protocol::admin::{Finding, Inconsistency, ReconcileAction, Resolution};
+    use std::{
+        path::Path,
+        time::{Duration, SystemTime},
+    };
+
+    /// writes a file last modified `age` seconds ago
+    fn write(path: &Path, contents: &[u8], age: u64) {
+        std::fs::write(path, contents).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(age))
+            .unwrap();
+    }
+
+    fn storage(directory: &Path) -> Storage {
+        let config: Config = serde_json::from_str("{}").unwrap();
+        Storage::new(&config, Some(directory))
+    }
+
+    fn found(findings: &[Finding]) -> Vec<(String, Inconsistency, Resolution)> {
+        let mut found: Vec<(String, Inconsistency, Resolution)> = findings
+            .iter()
+            .map(|finding| {
+                let path = Path::new(&finding.path);
+                (
+                    path.file_name().unwrap().to_string_lossy().into_owned(),
+                    finding.inconsistency,
+                    finding.resolution,
+                )
+            })
+            .collect();
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+        found
+    }
+
+    #[tokio::test]
+    async fn classification() {
+        let directory = tempfile::tempdir().unwrap();
+        let data = directory.path();
+        // complete
+        write(&data.join("100.jpg"), b"jpg", 600);
+        write(&data.join("100.mp4"), b"mp4", 600);
+        // found
+        write(&data.join("200.jpg"), b"jpg", 600);
+        write(&data.join("300.mp4"), b"mp4", 600);
+        write(&data.join("400.jpg"), b"", 600);
+        write(&data.join("400.mp4"), b"mp4", 600);
+        write(&data.join("500.jpg"), b"jpg", 600);
+        write(&data.join("500.mp4"), b"", 600);
+        write(&data.join("600.jpg"), b"jpg", 600);
+        write(&data.join("600.recording.mp4"), b"mp4", 600);
+        write(&data.join("notes.txt"), b"txt", 600);
+        // being captured, within the grace period
+        write(&data.join("700.jpg"), b"jpg", 0);
+        write(&data.join("800.jpg"), b"jpg", 600);
+        write(&data.join("800.recording.mp4"), b"mp4", 0);
+        write(&data.join("800.jpg.tmp"), b"jpg", 0);
+        // never looked into
+        for subdirectory in ["trash", "quarantine"] {
+            std::fs::create_dir(data.join(subdirectory)).unwrap();
+            write(&data.join(subdirectory).join("900.jpg"), b"jpg", 600);
+        }
+
+        let storage = storage(data);
+        let findings = reconcile(&storage, &Pins::default(), ReconcileAction::Report).await;
+        assert_eq!(
+            found(&findings),
+            vec![
+                (
+                    String::from("200.jpg"),
+                    Inconsistency::OrphanedThumbnail,
+                    Resolution::Reported
+                ),
+                (
+                    String::from("300.mp4"),
+                    Inconsistency::OrphanedClip,
+                    Resolution::Reported
+                ),
+                (
+                    String::from("400.jpg"),
+                    Inconsistency::EmptyThumbnail,
+                    Resolution::Reported
+                ),
+                (
+                    String::from("500.jpg"),
+                    Inconsistency::OrphanedThumbnail,
+                    Resolution::Reported
+                ),
+                (
+                    String::from("500.mp4"),
+                    Inconsistency::EmptyClip,
+                    Resolution::Reported
+                ),
+                (
+                    String::from("600.recording.mp4"),
+                    Inconsistency::StaleRecording,
+                    Resolution::Reported
+                ),
+                (
+                    String::from("notes.txt"),
+                    Inconsistency::UnknownFile,
+                    Resolution::Reported
+                ),
+            ]
+        );
+        assert!(findings
+            .iter()
+            .all(|finding| Path::new(&finding.path).exists()));
+    }
+
+    #[tokio::test]
+    async fn pins() {
+        let directory = tempfile::tempdir().unwrap();
+        let data = directory.path();
+        write(&data.join("100.jpg"), b"jpg", 600);
+        write(&data.join("200.jpg"), b"jpg", 600);
+
+        let mut pins = Pins::default();
+        pins.set(100, true);
+        let storage = storage(data);
+        let findings = reconcile(&storage, &pins, ReconcileAction::Quarantine).await;
+        assert_eq!(
+            found(&findings),
+            vec![
+                (
+                    String::from("100.jpg"),
+                    Inconsistency::OrphanedThumbnail,
+                    Resolution::Reported
+                ),
+                (
+                    String::from("200.jpg"),
+                    Inconsistency::OrphanedThumbnail,
+                    Resolution::Quarantined
+                ),
+            ]
+        );
+        assert!(data.join("100.jpg").exists());
+        assert!(!data.join("200.jpg").exists());
+        assert!(data.join("quarantine").join("200.jpg").exists());
+    }
+}
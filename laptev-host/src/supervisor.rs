@@ -0,0 +1,178 @@
+use laptev_protocol::capture::{CaptureState, CaptureStatus};
+use std::{process::Stdio, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::Command,
+    time::Instant,
+};
+
+use crate::{
+    data::{internal::SharedState, storage::Kind},
+    utils::get_timestamp,
+};
+
+/// how long to wait before restarting a process that just exited, doubled on every consecutive crash
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+/// a process that ran for at least this long is restarted after MIN_BACKOFF again
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+/// tell the capture process where to store the thumbnails and clips
+const THUMBNAIL_DIRECTORY_VARIABLE: &str = "LAPTEV_THUMBNAIL_DIRECTORY";
+const CLIP_DIRECTORY_VARIABLE: &str = "LAPTEV_CLIP_DIRECTORY";
+
+/// what is known about the capture process, kept in the AppState
+pub struct Capture {
+    state: CaptureState,
+    pid: Option<u32>,
+    started_at: Option<u64>,
+    restarts: u32,
+    last_exit_code: Option<i32>,
+    restart_at: Option<u64>,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Self {
+            state: CaptureState::Disabled,
+            pid: None,
+            started_at: None,
+            restarts: 0,
+            last_exit_code: None,
+            restart_at: None,
+        }
+    }
+}
+
+impl Capture {
+    fn started(&mut self, pid: Option<u32>) {
+        self.state = CaptureState::Running;
+        self.pid = pid;
+        self.started_at = Some(get_timestamp());
+        self.restart_at = None;
+    }
+    fn exited(&mut self, exit_code: Option<i32>, backoff: Duration) {
+        self.state = CaptureState::Restarting;
+        self.pid = None;
+        self.started_at = None;
+        self.last_exit_code = exit_code;
+        self.restart_at = Some(get_timestamp() + backoff.as_secs());
+        self.restarts += 1;
+    }
+    pub fn status(&self) -> CaptureStatus {
+        CaptureStatus {
+            state: self.state,
+            pid: self.pid,
+            uptime: self
+                .started_at
+                .map(|started_at| get_timestamp().saturating_sub(started_at)),
+            restarts: self.restarts,
+            last_exit_code: self.last_exit_code,
+            restart_at: self.restart_at,
+        }
+    }
+}
+
+/// runs the capture command as a child process for as long as the host runs, restarting it whenever it exits,
+/// its output is forwarded to the host's log
+pub fn supervise(state: SharedState, command: Vec<String>) {
+    let Some((program, arguments)) = command.split_first() else {
+        tracing::info!("no capture command configured, motion capture is not supervised");
+        return;
+    };
+    let (program, arguments) = (program.clone(), arguments.to_vec());
+
+    tokio::spawn(async move {
+        let storage = state.read().await.storage.clone();
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            let started = Instant::now();
+            let mut command = Command::new(&program);
+            // kill_on_drop only covers the host exiting on its own, a host that was killed would otherwise
+            // leave the capture process behind, and the next one would start a second one competing for the camera
+            #[cfg(target_os = "linux")]
+            // SAFETY: stop_with_host only makes async-signal-safe calls
+            unsafe {
+                command.pre_exec(stop_with_host(std::process::id()));
+            }
+            let result = command
+                .args(&arguments)
+                // python only flushes its output line by line when writing to a terminal
+                .env("PYTHONUNBUFFERED", "1")
+                .env(
+                    THUMBNAIL_DIRECTORY_VARIABLE,
+                    storage.directory(Kind::Thumbnail),
+                )
+                .env(CLIP_DIRECTORY_VARIABLE, storage.directory(Kind::Clip))
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn();
+
+            let exit_code = match result {
+                Ok(mut child) => {
+                    tracing::info!("started the capture process ({:?})", child.id());
+                    state.write().await.capture.started(child.id());
+                    if let Some(stdout) = child.stdout.take() {
+                        tokio::spawn(forward(stdout, false));
+                    }
+                    if let Some(stderr) = child.stderr.take() {
+                        tokio::spawn(forward(stderr, true));
+                    }
+                    match child.wait().await {
+                        Ok(status) => {
+                            tracing::warn!("the capture process exited, {}", status);
+                            status.code()
+                        }
+                        Err(error) => {
+                            tracing::warn!("lost track of the capture process\n{}", error);
+                            None
+                        }
+                    }
+                }
+                Err(error) => {
+                    tracing::warn!("failed to start the capture process\n{}", error);
+                    None
+                }
+            };
+
+            if started.elapsed() >= STABLE_AFTER {
+                backoff = MIN_BACKOFF;
+            }
+            state.write().await.capture.exited(exit_code, backoff);
+            tracing::info!("restarting the capture process in {:?}", backoff);
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    });
+}
+
+/// run in the capture process before the capture command, has the kernel send it SIGTERM as soon as the host dies
+#[cfg(target_os = "linux")]
+fn stop_with_host(host: u32) -> impl FnMut() -> std::io::Result<()> + Send + Sync + 'static {
+    move || {
+        // SAFETY: prctl and getppid are async-signal-safe
+        unsafe {
+            if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            // the host may have died before the signal was requested
+            if libc::getppid() as u32 != host {
+                return Err(std::io::Error::other("the host exited"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// logs every line the capture process writes, as warnings if it writes them to stderr
+async fn forward(output: impl AsyncRead + Unpin, stderr: bool) {
+    let mut lines = BufReader::new(output).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if stderr {
+            tracing::warn!("[motioncapture] {}", line);
+        } else {
+            tracing::info!("[motioncapture] {}", line);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use laptev_protocol::admin::BanEntry;
+use std::{collections::HashMap, net::IpAddr};
+
+use crate::utils::get_timestamp;
+
+/// the longest an address can be made to wait between two key exchanges, before being banned
+const MAX_BACKOFF: u64 = 300;
+
+/// keeps track of the key exchanges each address performed without ever authenticating
+///
+/// every key exchange counts as a failure until the client proves it knows the password,
+/// as a client guessing a wrong password usually notices it before even sending its confirmation
+#[derive(Default)]
+pub struct Throttle {
+    attempts: HashMap<IpAddr, Attempts>,
+}
+
+struct Attempts {
+    failures: u32,
+    retry_after: u64,
+    banned: bool,
+}
+
+impl Throttle {
+    /// returns the amount of seconds to wait for if the address is not allowed to perform a key exchange yet
+    pub fn check(&mut self, addr: IpAddr) -> Result<(), u64> {
+        let current_time = get_timestamp();
+        match self.attempts.get(&addr) {
+            Some(attempts) if attempts.retry_after > current_time => {
+                Err(attempts.retry_after - current_time)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// records a key exchange, which remains a failure until Self::succeed() is called for this address,
+    /// then bans the address if it reached the maximum amount of failures
+    pub fn fail(&mut self, addr: IpAddr, max_failures: u32, ban_duration: u64) {
+        let current_time = get_timestamp();
+        let attempts = self.attempts.entry(addr).or_insert(Attempts {
+            failures: 0,
+            retry_after: 0,
+            banned: false,
+        });
+        // a ban that ran out gives the address a clean slate
+        if attempts.banned {
+            attempts.failures = 0;
+            attempts.banned = false;
+        }
+        attempts.failures += 1;
+
+        if attempts.failures >= max_failures {
+            attempts.banned = true;
+            attempts.retry_after = current_time + ban_duration;
+            tracing::warn!(
+                "{:?} banned for {} seconds after {} failed handshakes",
+                addr,
+                ban_duration,
+                attempts.failures
+            );
+        } else {
+            // the first attempt is free, the following ones wait 1, 2, 4, ... seconds
+            let backoff = match attempts.failures {
+                1 => 0,
+                failures => 1_u64
+                    .checked_shl(failures - 2)
+                    .unwrap_or(MAX_BACKOFF)
+                    .min(MAX_BACKOFF),
+            };
+            attempts.retry_after = current_time + backoff;
+        }
+    }
+
+    /// forgets the failures of an address once it authenticated
+    pub fn succeed(&mut self, addr: IpAddr) {
+        self.attempts.remove(&addr);
+    }
+
+    /// forgets the addresses that have been quiet for long enough
+    pub fn update(&mut self, ban_duration: u64) {
+        let current_time = get_timestamp();
+        self.attempts
+            .retain(|_, attempts| attempts.retry_after + ban_duration > current_time)
+    }
+
+    pub fn entries(&self) -> Vec<BanEntry> {
+        self.attempts
+            .iter()
+            .map(|(addr, attempts)| BanEntry {
+                addr: *addr,
+                failures: attempts.failures,
+                retry_after: attempts.retry_after,
+                banned: attempts.banned && attempts.retry_after > get_timestamp(),
+            })
+            .collect()
+    }
+}
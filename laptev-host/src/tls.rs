@@ -0,0 +1,121 @@
+use anyhow::Context;
+use axum_server::tls_rustls::RustlsConfig;
+use laptev_protocol::identity::certificate_fingerprint;
+use std::{path::Path, sync::Arc};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// the files the certificate and its key are stored in, next to the configuration
+const CERTIFICATE_FILE: &str = "laptev.cert";
+const KEY_FILE: &str = "laptev.key";
+
+/// the host's self-signed TLS certificate and its private key, both DER encoded,
+/// stored next to laptev.config as laptev.cert and laptev.key
+pub struct Certificate {
+    certificate: Vec<u8>,
+    key: Vec<u8>,
+}
+
+impl Certificate {
+    /// loads laptev.cert and laptev.key, or generates and saves them if neither exists,
+    /// they are never replaced otherwise, clients pinned the certificate, the host refuses to start instead
+    pub async fn new(directory: &Path) -> anyhow::Result<Self> {
+        let (certificate_path, key_path) =
+            (directory.join(CERTIFICATE_FILE), directory.join(KEY_FILE));
+        let missing = !tokio::fs::try_exists(&certificate_path).await?
+            && !tokio::fs::try_exists(&key_path).await?;
+        let certificate = if missing {
+            tracing::warn!(
+                "no certificate found, generating {} and {}",
+                certificate_path.display(),
+                key_path.display()
+            );
+            let certificate = Self::generate()?;
+            // a new certificate on every launch would be as bad as a replaced one
+            certificate
+                .save(&certificate_path, &key_path)
+                .await
+                .context("failed to save the generated certificate")?;
+            certificate
+        } else {
+            let certificate = Self::load(&certificate_path, &key_path)
+                .await
+                .with_context(|| {
+                    format!(
+                        "failed to load {} and {}",
+                        certificate_path.display(),
+                        key_path.display()
+                    )
+                })?;
+            tracing::info!("certificate loaded from {}", certificate_path.display());
+            certificate
+        };
+        tracing::info!(
+            "certificate fingerprint : {}",
+            certificate_fingerprint(&certificate.certificate)
+        );
+        Ok(certificate)
+    }
+
+    pub fn rustls_config(&self) -> anyhow::Result<RustlsConfig> {
+        let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![self.certificate.clone().into()],
+            rustls::pki_types::PrivatePkcs8KeyDer::from(self.key.clone()).into(),
+        )?;
+        Ok(RustlsConfig::from_config(Arc::new(config)))
+    }
+
+    async fn save(&self, certificate_path: &Path, key_path: &Path) -> anyhow::Result<()> {
+        // never truncates a certificate, even one that could not be loaded
+        tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(certificate_path)
+            .await?
+            .write_all(&self.certificate)
+            .await?;
+
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        options.open(key_path).await?.write_all(&self.key).await?;
+
+        Ok(())
+    }
+
+    async fn load(certificate_path: &Path, key_path: &Path) -> anyhow::Result<Self> {
+        let mut certificate: Vec<u8> = Vec::with_capacity(1024);
+        tokio::fs::OpenOptions::new()
+            .create(false)
+            .read(true)
+            .open(certificate_path)
+            .await?
+            .read_to_end(&mut certificate)
+            .await?;
+
+        let mut key: Vec<u8> = Vec::with_capacity(256);
+        tokio::fs::OpenOptions::new()
+            .create(false)
+            .read(true)
+            .open(key_path)
+            .await?
+            .read_to_end(&mut key)
+            .await?;
+
+        Ok(Self { certificate, key })
+    }
+
+    fn generate() -> anyhow::Result<Self> {
+        let certificate = rcgen::generate_simple_self_signed(vec![String::from("laptev-host")])?;
+        Ok(Self {
+            certificate: certificate.serialize_der()?,
+            key: certificate.serialize_private_key_der(),
+        })
+    }
+}
@@ -1,6 +1,7 @@
-use rand::{rngs::StdRng, RngCore, SeedableRng};
 use std::time::{Duration, SystemTime};
 
+use crate::data::{index, internal::SharedState};
+
 pub fn get_timestamp() -> u64 {
     SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
@@ -8,40 +9,59 @@ pub fn get_timestamp() -> u64 {
         .as_secs()
 }
 
-pub fn get_rng() -> StdRng {
-    rand::rngs::StdRng::from_entropy()
-}
+/// removes the files of every event captured at least `file_expiration_time` seconds ago
+pub async fn clean_older_than(state: &SharedState) {
+    let (seconds, storage) = {
+        let read_state = state.read().await;
+        (
+            read_state.config.file_expiration_time,
+            read_state.storage.clone(),
+        )
+    };
+    let cutoff = get_timestamp().saturating_sub(seconds);
+    let expired = state.read().await.index.up_to(cutoff);
+    for timestamp in expired.into_iter() {
+        storage.remove_event(timestamp).await;
+        state.write().await.index.remove(timestamp);
+    }
 
-pub fn rng_fill_bytes(bytes: &mut [u8]) {
-    get_rng().fill_bytes(bytes);
+    // pinned events never expire, but the pins of those whose files were removed some other way would stay forever
+    let stale: Vec<u64> = {
+        let read_state = state.read().await;
+        read_state
+            .index
+            .pins()
+            .timestamps()
+            .filter(|timestamp| *timestamp <= cutoff && !read_state.index.contains(*timestamp))
+            .collect()
+    };
+    index::unpin(state, &stale).await;
 }
 
-pub async fn clean_older_than(seconds: u64) {
-    let current_timestamp = get_timestamp();
-    if let Ok(mut read_dir) = tokio::fs::read_dir("./data").await {
-        while let Ok(Some(entry)) = read_dir.next_entry().await {
-            let entry = entry.path();
-            if entry.extension().is_none() {
-                continue;
-            }
-            if entry.extension().unwrap().to_str().is_none() {
-                continue;
-            }
-            if entry.file_stem().is_none() {
-                continue;
-            }
-            if entry.file_stem().unwrap().to_str().is_none() {
-                continue;
-            }
-            if let Ok(timestamp) = entry.file_stem().unwrap().to_str().unwrap().parse::<u64>() {
-                if current_timestamp - timestamp < seconds {
-                    continue;
-                }
-                match tokio::fs::remove_file(entry).await {
-                    Ok(()) => tracing::info!("removed a file older than {}", seconds),
-                    Err(error) => tracing::warn!("failed to remove an old file\n{}", error),
-                }
-            }
+/// purges the events deleted at least `trash_expiration_time` seconds ago
+pub async fn empty_trash(state: &SharedState) {
+    let (seconds, storage) = {
+        let read_state = state.read().await;
+        (
+            read_state.config.trash_expiration_time,
+            read_state.storage.clone(),
+        )
+    };
+    let now = get_timestamp();
+    let mut purged: Vec<u64> = Vec::new();
+    for (timestamp, trashed) in storage.trashed().await.into_iter() {
+        if trashed.deleted_at.saturating_add(seconds) <= now {
+            storage.purge(timestamp).await;
+            purged.push(timestamp);
         }
     }
+    // deleting an event already drops its pin, unless saving the pins failed then
+    let purged: Vec<u64> = {
+        let read_state = state.read().await;
+        purged
+            .into_iter()
+            .filter(|timestamp| !read_state.index.contains(*timestamp))
+            .collect()
+    };
+    index::unpin(state, &purged).await;
 }
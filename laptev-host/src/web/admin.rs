@@ -0,0 +1,159 @@
+use crate::{data::internal::SharedState, error::Error, web::session::Session};
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use laptev_protocol::{
+    action::Action,
+    admin::{BansResponse, ReconcileAction, ReconcileRequest},
+    audit::{AuditEvent, AuditRequest},
+    endpoint,
+    role::Permission,
+    session::SessionId,
+    EncryptedMessage,
+};
+use tower_http::trace::{self, TraceLayer};
+use tracing::Level;
+
+pub fn routes_admin(state: SharedState) -> Router {
+    Router::new()
+        .route(endpoint::AUDIT, get(audit))
+        .with_state(state.clone())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
+                .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .route(endpoint::BANS, get(bans))
+        .with_state(state.clone())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
+                .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .route(endpoint::RECONCILE, get(inconsistencies).post(reconcile))
+        .with_state(state.clone())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
+                .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
+        )
+}
+
+/// returns the audit records matching the query, only to administrators
+async fn audit(
+    State(state): State<SharedState>,
+    Session(session): Session,
+    Query(parameters): Query<AuditRequest>,
+) -> impl IntoResponse {
+    let (cipher, audit) = {
+        let read_state = state.read().await;
+        let client = read_state.get_authorized(&session, Permission::Administer)?;
+        (client.cipher.clone(), read_state.audit.clone())
+    };
+
+    // the log is read without holding the state
+    let body = audit.query(&parameters).await.map_err(|error| {
+        tracing::warn!("{}", error);
+        Error::Internal
+    })?;
+
+    // unwrapping because this should never fail
+    let response = EncryptedMessage::new(&bincode::serialize(&body).unwrap(), &cipher).unwrap();
+    Ok::<_, Error>(Bytes::from(response.into_bytes()))
+}
+
+/// returns the addresses currently throttled or banned from the handshake, only to administrators
+async fn bans(State(state): State<SharedState>, Session(session): Session) -> impl IntoResponse {
+    let cipher = state
+        .read()
+        .await
+        .get_authorized(&session, Permission::Administer)?
+        .cipher
+        .clone();
+
+    // only then are the expired bans dropped, which needs the write lock
+    let body: BansResponse = {
+        let mut write_state = state.write().await;
+        write_state.update();
+        write_state.throttle.entries()
+    };
+
+    // unwrapping because this should never fail
+    let response = EncryptedMessage::new(&bincode::serialize(&body).unwrap(), &cipher).unwrap();
+    Ok::<_, Error>(Bytes::from(response.into_bytes()))
+}
+
+/// reports the files of the data directories that are not part of a complete event, only to administrators
+async fn inconsistencies(
+    State(state): State<SharedState>,
+    Session(session): Session,
+) -> impl IntoResponse {
+    reconcile_with(state, session, ReconcileAction::Report).await
+}
+
+/// reports those files and quarantines or repairs them as requested, only to administrators
+async fn reconcile(
+    State(state): State<SharedState>,
+    Session(session): Session,
+    Query(parameters): Query<ReconcileRequest>,
+    body: Bytes,
+) -> Result<Bytes, Error> {
+    // checks that the client sealed this request, reconcile_with checks the rest
+    state.write().await.get_authorized_action(
+        &session,
+        Permission::Administer,
+        Action::Reconcile {
+            action: parameters.action,
+        },
+        &body,
+    )?;
+    reconcile_with(state, session, parameters.action).await
+}
+
+async fn reconcile_with(
+    state: SharedState,
+    session: SessionId,
+    action: ReconcileAction,
+) -> Result<Bytes, Error> {
+    let (addr, username, cipher, audit, storage, pins) = {
+        let read_state = state.read().await;
+        let client = read_state.get_authorized(&session, Permission::Administer)?;
+        (
+            client.addr,
+            client.username.clone(),
+            client.cipher.clone(),
+            read_state.audit.clone(),
+            read_state.storage.clone(),
+            read_state.index.pins().clone(),
+        )
+    };
+
+    // the files are scanned without holding the state, the watcher keeps the index up to date with what is moved
+    let body = crate::reconcile::reconcile(&storage, &pins, action).await;
+    if action != ReconcileAction::Report {
+        tracing::info!(
+            "RECONCILE REQUEST ({:?}) FROM {} ({:?})",
+            action,
+            username,
+            addr
+        );
+        audit
+            .record(
+                addr,
+                Some(username),
+                AuditEvent::Reconcile {
+                    action,
+                    findings: body.len(),
+                },
+            )
+            .await;
+    }
+
+    // unwrapping because this should never fail
+    let response = EncryptedMessage::new(&bincode::serialize(&body).unwrap(), &cipher).unwrap();
+    Ok(Bytes::from(response.into_bytes()))
+}
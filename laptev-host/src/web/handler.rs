@@ -1,37 +1,107 @@
 use crate::{
-    data::{external::EncryptedMessage, internal::SharedState},
+    data::{
+        index,
+        internal::SharedState,
+        storage::{Kind, Probe},
+    },
     error::Error,
+    reconcile,
+    web::session::Session,
 };
 use axum::{
-    body::Bytes,
-    extract::{ConnectInfo, Path, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::{delete as del, get},
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    routing::{delete as del, get, post, put},
     Router,
 };
-use std::{collections::HashMap, net::SocketAddr, path::PathBuf};
-use tokio::io::AsyncReadExt;
+use futures_util::StreamExt;
+use laptev_protocol::{
+    action::Action,
+    audit::AuditEvent,
+    delete::{DeleteResponse, DeletedFile},
+    download::DownloadRequest,
+    endpoint,
+    list::{ListRequest, ListResponse},
+    notification::Notification,
+    role::Permission,
+    session::SessionId,
+    stream::{encrypted_length, StreamEncryptor, CHUNK_SIZE},
+    sync::{SyncEntry, SyncRequest, SyncResponse},
+    trash::{TrashResponse, TrashedEvent},
+    EncryptedMessage,
+};
+use std::{convert::Infallible, io::SeekFrom, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::broadcast::error::RecvError,
+};
 use tower_http::trace::{self, TraceLayer};
 use tracing::Level;
 
 pub fn routes_handler(state: SharedState) -> Router {
     Router::new()
-        .route("/synchronize", get(synchronize))
+        .route(endpoint::SYNCHRONIZE, get(synchronize))
         .with_state(state.clone())
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
                 .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
         )
-        .route("/download/:id", get(download))
+        .route(endpoint::LIST, get(list))
         .with_state(state.clone())
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
                 .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
         )
-        .route("/delete/:id", del(delete))
+        .route(endpoint::THUMBNAIL, get(thumbnail))
+        .with_state(state.clone())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
+                .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .route(endpoint::DOWNLOAD, get(download))
+        .with_state(state.clone())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
+                .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .route(endpoint::DELETE, del(delete))
+        .with_state(state.clone())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
+                .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .route(endpoint::PIN, put(pin).delete(unpin))
+        .with_state(state.clone())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
+                .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .route(endpoint::TRASH, get(trash))
+        .with_state(state.clone())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
+                .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .route(endpoint::RESTORE, post(restore))
+        .with_state(state.clone())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
+                .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .route(endpoint::NOTIFICATIONS, get(notifications))
         .with_state(state.clone())
         .layer(
             TraceLayer::new_for_http()
@@ -40,162 +110,526 @@ pub fn routes_handler(state: SharedState) -> Router {
         )
 }
 
+/// how often a notification stream checks that its session is still valid, when nothing happens
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
 async fn synchronize(
     State(state): State<SharedState>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
-    Query(parameters): Query<HashMap<String, usize>>,
+    Session(session): Session,
+    Query(parameters): Query<SyncRequest>,
 ) -> impl IntoResponse {
-    // checks that the client is authenticated and gets their cipher
+    // checks that the client is allowed to view entries and gets their cipher
     let read_state = state.read().await;
-    let cipher = match read_state.db.get(&addr.ip()) {
-        Some(data) => {
-            if data.is_authenticated() {
-                Ok(&data.cipher)
-            } else {
-                Err(Error::NotAuthenticated)
-            }
+    let cipher = &read_state
+        .get_authorized(&session, Permission::View)?
+        .cipher;
+
+    // prepares the response
+    // events still being recorded are left out, this endpoint cannot tell clients they are incomplete
+    let (events, older, newer) = read_state.index.page(&parameters, |event| {
+        event.thumbnail_size.is_some() && !event.recording
+    });
+    let mut body = SyncResponse {
+        entries: Vec::new(),
+        older,
+        newer,
+    };
+
+    for (timestamp, _) in events.into_iter() {
+        if let Ok(data) = read_state.storage.read(timestamp, Kind::Thumbnail).await {
+            body.entries.push(SyncEntry {
+                timestamp,
+                thumbnail: data,
+            })
         }
-        None => Err(Error::NotAuthenticated),
-    }?;
+    }
+    // unwrapping because this should never fail
+    let response = EncryptedMessage::new(&bincode::serialize(&body).unwrap(), cipher).unwrap();
+    Ok::<_, Error>(Bytes::from(response.into_bytes()))
+}
+
+/// lists events without their thumbnails, so that clients only fetch the thumbnails they do not have yet
+async fn list(
+    State(state): State<SharedState>,
+    Session(session): Session,
+    Query(parameters): Query<ListRequest>,
+) -> impl IntoResponse {
+    // checks that the client is allowed to view entries and gets their cipher
+    let read_state = state.read().await;
+    let cipher = &read_state
+        .get_authorized(&session, Permission::View)?
+        .cipher;
 
     // prepares the response
-    let mut entries: Vec<(u64, PathBuf)> = Vec::new();
+    let (events, older, newer) = read_state.index.page(&parameters, |_| true);
+    let body = ListResponse {
+        events: events
+            .into_iter()
+            .map(|(timestamp, event)| read_state.index.metadata(timestamp, &event))
+            .collect(),
+        older,
+        newer,
+    };
+    // unwrapping because this should never fail
+    let response = EncryptedMessage::new(&bincode::serialize(&body).unwrap(), cipher).unwrap();
+    Ok::<_, Error>(Bytes::from(response.into_bytes()))
+}
 
-    if let Ok(mut read_dir) = tokio::fs::read_dir("./data").await {
-        while let Ok(Some(entry)) = read_dir.next_entry().await {
-            let entry = entry.path();
-            if entry.extension().is_none() {
-                continue;
-            }
-            if entry.extension().unwrap().to_str().is_none() {
-                continue;
-            }
-            if entry.extension().unwrap().to_str().unwrap() != "jpg" {
-                continue;
-            }
+async fn thumbnail(
+    State(state): State<SharedState>,
+    Session(session): Session,
+    Path(id): Path<u64>,
+) -> impl IntoResponse {
+    // checks that the client is allowed to view entries and gets their cipher
+    let read_state = state.read().await;
+    let cipher = &read_state
+        .get_authorized(&session, Permission::View)?
+        .cipher;
 
-            if entry.file_stem().is_none() {
-                continue;
-            }
-            if entry.file_stem().unwrap().to_str().is_none() {
-                continue;
+    let data = read_state
+        .storage
+        .read(id, Kind::Thumbnail)
+        .await
+        .map_err(|error| match error.kind() {
+            std::io::ErrorKind::NotFound => Error::NotFound,
+            _ => {
+                tracing::warn!("{}", error);
+                Error::Internal
             }
+        })?;
 
-            if let Ok(timestamp) = entry.file_stem().unwrap().to_str().unwrap().parse::<u64>() {
-                entries.push((timestamp, entry))
-            }
-        }
-    }
-    entries.sort_by(|(a, _), (b, _)| b.cmp(a));
+    // unwrapping because this should never fail
+    let response = EncryptedMessage::new(&data, cipher).unwrap();
+    Ok::<_, Error>(Bytes::from(response.into_bytes()))
+}
 
-    // the amount of entries to skip, if any
-    let skip = match parameters.get("skip") {
-        Some(s) => *s,
-        None => 0,
+/// pushes new, completed and deleted events to the client as they happen, until its session expires
+async fn notifications(
+    State(state): State<SharedState>,
+    Session(session): Session,
+) -> impl IntoResponse {
+    // checks that the client is allowed to view entries and gets their cipher,
+    // the lock is not held while the stream is open
+    let (cipher, receiver) = {
+        let read_state = state.read().await;
+        let client = read_state.get_authorized(&session, Permission::View)?;
+        (client.cipher.clone(), read_state.index.subscribe())
     };
-    if skip > entries.len() {
-        entries.drain(..);
-    } else {
-        entries.drain(0..skip);
-    }
 
-    // the maximum amount of entries to send back to the client
-    let size = match parameters.get("size") {
-        Some(s) => *s,
-        None => 25,
+    let stream = futures_util::stream::unfold(
+        (state, receiver, cipher),
+        move |(state, mut receiver, cipher)| async move {
+            loop {
+                let result = tokio::time::timeout(SESSION_CHECK_INTERVAL, receiver.recv()).await;
+                state
+                    .read()
+                    .await
+                    .get_authorized(&session, Permission::View)
+                    .ok()?;
+                let notification: Notification = match result {
+                    Ok(Ok(notification)) => notification,
+                    Ok(Err(RecvError::Lagged(count))) => {
+                        tracing::warn!("a client missed {} notifications", count);
+                        continue;
+                    }
+                    Ok(Err(RecvError::Closed)) => return None,
+                    Err(..) => continue,
+                };
+                // unwrapping because this should never fail
+                let event = Event::default().data(notification.seal(&cipher).unwrap());
+                return Some((Ok::<_, Infallible>(event), (state, receiver, cipher)));
+            }
+        },
+    );
+    Ok::<_, Error>(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// moves the event to the trash, from which it can be restored until it is purged,
+/// unless trash_expiration_time is 0, and tells the client what became of each of its files
+async fn delete(
+    State(state): State<SharedState>,
+    Session(session): Session,
+    Path(id): Path<u64>,
+    body: Bytes,
+) -> impl IntoResponse {
+    // checks that the client is allowed to delete entries and sealed this request, and gets their cipher
+    let (addr, username, cipher, audit, storage, trash_expiration_time) = {
+        let mut write_state = state.write().await;
+        let client = write_state.get_authorized_action(
+            &session,
+            Permission::Delete,
+            Action::Delete { id },
+            &body,
+        )?;
+        let (addr, username, cipher) =
+            (client.addr, client.username.clone(), client.cipher.clone());
+        (
+            addr,
+            username,
+            cipher,
+            write_state.audit.clone(),
+            write_state.storage.clone(),
+            write_state.config.trash_expiration_time,
+        )
     };
-    if entries.len() > size {
-        entries.drain(size..entries.len());
-    }
 
-    let mut body: Vec<(u64, Vec<u8>)> = Vec::new();
+    // logs the request
+    tracing::info!(
+        "DELETE REQUEST FOR ENTRY {} FROM {} ({:?})",
+        id,
+        username,
+        addr
+    );
 
-    for (timestamp, filepath) in entries.into_iter() {
-        if let Ok(mut file) = tokio::fs::OpenOptions::new()
-            .create(false)
-            .read(true)
-            .open(filepath)
-            .await
+    // attempts to delete the request, its files are moved to the trash together (and purged right away if there is no trash),
+    // so that the event is never left half-deleted
+    let result = match storage.modified(id, Kind::Recording).await {
+        // its clip would come back as soon as the recording completes,
+        // one left untouched for longer than reconcile's grace period was interrupted and goes with the rest of the event
+        Ok(Some(modified))
+            if modified
+                .elapsed()
+                .map_or(true, |age| age < reconcile::GRACE_PERIOD) =>
         {
-            let mut data: Vec<u8> = Vec::new();
-            if file.read_to_end(&mut data).await.is_ok() {
-                body.push((timestamp, data))
+            Err(Error::Recording)
+        }
+        Ok(..) => match storage.trash(id).await {
+            Ok(trashed) if trashed.is_empty() => Err(Error::NotFound),
+            Ok(trashed) => {
+                let removed = match trash_expiration_time {
+                    0 => storage.purge(id).await,
+                    _ => Vec::new(),
+                };
+                let deleted = |kind: Kind| match (trashed.contains(&kind), removed.contains(&kind))
+                {
+                    (_, true) => DeletedFile::Removed,
+                    (true, false) => DeletedFile::Trashed,
+                    (false, false) => DeletedFile::Missing,
+                };
+                Ok(DeleteResponse {
+                    thumbnail: deleted(Kind::Thumbnail),
+                    // what there was of the clip, if it was never completed
+                    clip: match deleted(Kind::Clip) {
+                        DeletedFile::Missing => deleted(Kind::Recording),
+                        clip => clip,
+                    },
+                })
+            }
+            Err(error) => {
+                tracing::warn!(
+                    "failed to delete an event, it was left as it was\n{}",
+                    error
+                );
+                Err(Error::Internal)
             }
+        },
+        Err(error) => {
+            tracing::warn!("{}", error);
+            Err(Error::Internal)
         }
-    }
+    };
+    audit
+        .record(
+            addr,
+            Some(username),
+            AuditEvent::Delete {
+                id,
+                success: result.is_ok(),
+            },
+        )
+        .await;
+    let body = result?;
+    state.write().await.index.remove(id);
+    // restoring the event does not bring its pin back
+    index::unpin(&state, &[id]).await;
+
     // unwrapping because this should never fail
-    let response = EncryptedMessage::new(&bincode::serialize(&body).unwrap(), cipher).unwrap();
+    let response = EncryptedMessage::new(&bincode::serialize(&body).unwrap(), &cipher).unwrap();
     Ok::<_, Error>(Bytes::from(response.into_bytes()))
 }
 
-async fn delete(
+/// lists the deleted events that can still be restored
+async fn trash(State(state): State<SharedState>, Session(session): Session) -> impl IntoResponse {
+    // checks that the client is allowed to delete entries, and so to restore them, and gets their cipher
+    let (cipher, storage, trash_expiration_time) = {
+        let read_state = state.read().await;
+        let client = read_state.get_authorized(&session, Permission::Delete)?;
+        (
+            client.cipher.clone(),
+            read_state.storage.clone(),
+            read_state.config.trash_expiration_time,
+        )
+    };
+
+    let mut body: TrashResponse = storage
+        .trashed()
+        .await
+        .into_iter()
+        .map(|(timestamp, trashed)| TrashedEvent {
+            timestamp,
+            thumbnail_size: trashed.thumbnail_size,
+            clip_size: trashed.clip_size,
+            deleted_at: trashed.deleted_at,
+            purge_at: trashed.deleted_at.saturating_add(trash_expiration_time),
+        })
+        .collect();
+    body.sort_unstable_by_key(|event| std::cmp::Reverse(event.deleted_at));
+    // unwrapping because this should never fail
+    let response = EncryptedMessage::new(&bincode::serialize(&body).unwrap(), &cipher).unwrap();
+    Ok::<_, Error>(Bytes::from(response.into_bytes()))
+}
+
+/// moves a deleted event back out of the trash, clients are notified of it as if it was new
+async fn restore(
     State(state): State<SharedState>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Session(session): Session,
     Path(id): Path<u64>,
+    body: Bytes,
 ) -> impl IntoResponse {
-    // checks that the client is authenticated
-    if let Some(client) = state.read().await.db.get(&addr.ip()) {
-        if !client.is_authenticated() {
-            return Err(Error::NotAuthenticated);
+    // checks that the client is allowed to delete entries, and so to restore them, and sealed this request
+    let (addr, username, audit, storage) = {
+        let mut write_state = state.write().await;
+        let client = write_state.get_authorized_action(
+            &session,
+            Permission::Delete,
+            Action::Restore { id },
+            &body,
+        )?;
+        let (addr, username) = (client.addr, client.username.clone());
+        (
+            addr,
+            username,
+            write_state.audit.clone(),
+            write_state.storage.clone(),
+        )
+    };
+
+    // logs the request
+    tracing::info!(
+        "RESTORE REQUEST FOR ENTRY {} FROM {} ({:?})",
+        id,
+        username,
+        addr
+    );
+
+    let result = match storage.restore(id).await {
+        Ok(restored) if restored.is_empty() => Err(Error::NotFound),
+        Ok(restored) => {
+            // indexed right away, the watcher would tell clients about the thumbnail and clip one at a time
+            let mut probes: Vec<Probe> = Vec::new();
+            for kind in restored.into_iter() {
+                if let Some(probe) = storage.probe(&storage.path(id, kind)).await {
+                    probes.push(probe);
+                }
+            }
+            state.write().await.index.apply_all(id, probes);
+            Ok(StatusCode::OK)
         }
-    } else {
-        return Err(Error::NotAuthenticated);
-    }
+        Err(error) => {
+            tracing::warn!("failed to restore an event from the trash\n{}", error);
+            Err(Error::Internal)
+        }
+    };
+    audit
+        .record(
+            addr,
+            Some(username),
+            AuditEvent::Restore {
+                id,
+                success: result.is_ok(),
+            },
+        )
+        .await;
+    result
+}
+
+/// pins the event, it is kept whatever its age and the storage budget until it is unpinned
+async fn pin(
+    State(state): State<SharedState>,
+    Session(session): Session,
+    Path(id): Path<u64>,
+    body: Bytes,
+) -> impl IntoResponse {
+    set_pin(state, session, id, true, &body).await
+}
+
+async fn unpin(
+    State(state): State<SharedState>,
+    Session(session): Session,
+    Path(id): Path<u64>,
+    body: Bytes,
+) -> impl IntoResponse {
+    set_pin(state, session, id, false, &body).await
+}
+
+async fn set_pin(
+    state: SharedState,
+    session: SessionId,
+    id: u64,
+    pinned: bool,
+    body: &[u8],
+) -> Result<StatusCode, Error> {
+    // checks that the client is allowed to decide which entries are kept, like it may delete them, and sealed this request
+    let (addr, username, audit, exists) = {
+        let mut write_state = state.write().await;
+        let client = write_state.get_authorized_action(
+            &session,
+            Permission::Delete,
+            Action::Pin { id, pinned },
+            body,
+        )?;
+        let (addr, username) = (client.addr, client.username.clone());
+        (
+            addr,
+            username,
+            write_state.audit.clone(),
+            write_state.index.contains(id),
+        )
+    };
 
     // logs the request
-    tracing::info!("DELETE REQUEST FOR ENTRY {} FROM {:?}", id, addr);
-
-    // attempts to delete the request
-    let filepaths = [
-        PathBuf::from(format!("./data/{}.jpg", id)),
-        PathBuf::from(format!("./data/{}.mp4", id)),
-    ];
-    if tokio::fs::remove_file(&filepaths[0]).await.is_ok()
-        && tokio::fs::remove_file(&filepaths[1]).await.is_ok()
-    {
-        Ok(StatusCode::OK)
-    } else {
-        Err(Error::Internal)
+    tracing::info!(
+        "{} REQUEST FOR ENTRY {} FROM {} ({:?})",
+        if pinned { "PIN" } else { "UNPIN" },
+        id,
+        username,
+        addr
+    );
+
+    // only existing events can be pinned, any pin can be removed
+    if pinned && !exists {
+        return Err(Error::NotFound);
+    }
+    let result = index::update_pins(&state, |pins| {
+        if pins.set(id, pinned) {
+            vec![id]
+        } else {
+            Vec::new()
+        }
+    })
+    .await;
+
+    match result {
+        Ok(changed) => {
+            if !changed.is_empty() {
+                audit
+                    .record(addr, Some(username), AuditEvent::Pin { id, pinned })
+                    .await;
+            }
+            Ok(StatusCode::OK)
+        }
+        Err(error) => {
+            tracing::warn!("failed to save the pins\n{}", error);
+            Err(Error::Internal)
+        }
     }
 }
 
 async fn download(
     State(state): State<SharedState>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Session(session): Session,
     Path(id): Path<u64>,
+    Query(parameters): Query<DownloadRequest>,
 ) -> impl IntoResponse {
-    // checks that the client is authenticated and gets their cipher
-    let read_state = state.read().await;
-    let cipher = match read_state.db.get(&addr.ip()) {
-        Some(data) => {
-            if data.is_authenticated() {
-                Ok(&data.cipher)
+    // checks that the client is allowed to download clips and gets their cipher,
+    // the lock is not held while the clip is being sent
+    let (addr, username, cipher, audit, storage) = {
+        let read_state = state.read().await;
+        let client = read_state.get_authorized(&session, Permission::Download)?;
+        (
+            client.addr,
+            client.username.clone(),
+            client.cipher.clone(),
+            read_state.audit.clone(),
+            read_state.storage.clone(),
+        )
+    };
+
+    let result = storage.open(id, Kind::Clip).await;
+    let result = match result {
+        Ok(file) => file.metadata().await.map(|metadata| (file, metadata.len())),
+        Err(error) => Err(error),
+    };
+    // resumes from the requested chunk, if any
+    let result = match result {
+        Ok((file, length)) => match parameters.first_chunk() {
+            Some(first_chunk) if parameters.offset <= length => Ok((file, length, first_chunk)),
+            _ => Err(Error::InvalidRange),
+        },
+        // clips still being recorded are not there yet
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Err(Error::NotFound),
+        Err(error) => {
+            tracing::warn!("{}", error);
+            Err(Error::Internal)
+        }
+    };
+    audit
+        .record(
+            addr,
+            Some(username),
+            AuditEvent::Download {
+                id,
+                success: result.is_ok(),
+            },
+        )
+        .await;
+    let (mut file, length, first_chunk) = result?;
+
+    if parameters.offset != 0 {
+        file.seek(SeekFrom::Start(parameters.offset))
+            .await
+            .map_err(|error| {
+                tracing::warn!("{}", error);
+                Error::Internal
+            })?;
+    }
+    let status = match parameters.offset {
+        0 => StatusCode::OK,
+        _ => StatusCode::PARTIAL_CONTENT,
+    };
+
+    // the clip is read, encrypted and sent one chunk at a time
+    let encryptor = StreamEncryptor::starting_at(id, first_chunk);
+    let header = Bytes::copy_from_slice(&encryptor.header());
+    let chunks =
+        futures_util::stream::unfold(Some((file, encryptor, cipher)), |state| async move {
+            let (mut file, mut encryptor, cipher) = state?;
+            let mut chunk: Vec<u8> = vec![0; CHUNK_SIZE];
+            let length = match read_chunk(&mut file, &mut chunk).await {
+                Ok(length) => length,
+                Err(error) => return Some((Err(error), None)),
+            };
+            // unwrapping because this should never fail
+            if length == CHUNK_SIZE {
+                let data = encryptor.encrypt_next(&chunk, &cipher).unwrap();
+                Some((Ok(Bytes::from(data)), Some((file, encryptor, cipher))))
             } else {
-                Err(Error::NotAuthenticated)
+                let data = encryptor.encrypt_last(&chunk[..length], &cipher).unwrap();
+                Some((Ok(Bytes::from(data)), None))
             }
-        }
-        None => Err(Error::NotAuthenticated),
-    }?;
+        });
+    let body = Body::from_stream(
+        futures_util::stream::once(async move { Ok::<_, std::io::Error>(header) }).chain(chunks),
+    );
 
-    // prepares the response
-    let mut body: Vec<u8> = Vec::new();
-    tokio::fs::OpenOptions::new()
-        .create(false)
-        .read(true)
-        .open(format!("./data/{}.mp4", id))
-        .await
-        .map_err(|error| {
-            tracing::warn!("{}", error);
-            Error::Internal
-        })?
-        .read_to_end(&mut body)
-        .await
-        .map_err(|error| {
-            tracing::warn!("{}", error);
-            Error::Internal
-        })?;
+    Ok::<_, Error>((
+        status,
+        [(
+            header::CONTENT_LENGTH,
+            encrypted_length(length - parameters.offset).to_string(),
+        )],
+        body,
+    ))
+}
 
-    // unwrapping because this should never fail
-    let response = EncryptedMessage::new(&body, cipher).unwrap();
-    Ok::<_, Error>(Bytes::from(response.into_bytes()))
+/// fills the buffer as much as the file allows, returns how many bytes were read
+async fn read_chunk(file: &mut tokio::fs::File, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled: usize = 0;
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..]).await? {
+            0 => break,
+            length => filled += length,
+        }
+    }
+    Ok(filled)
 }
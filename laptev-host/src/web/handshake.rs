@@ -1,7 +1,4 @@
-use crate::{
-    data::{external::EncryptedMessage, internal::SharedState},
-    error::Error,
-};
+use crate::{config::Account, data::internal::SharedState, error::Error, web::session::Session};
 use axum::{
     body::Bytes,
     extract::{ConnectInfo, Path, State},
@@ -9,15 +6,26 @@ use axum::{
     routing::put,
     Router,
 };
-use rand::{rngs::StdRng, SeedableRng};
+use laptev_protocol::{
+    audit::AuditEvent,
+    endpoint,
+    handshake::{
+        AuthenticationRequest, AuthenticationResponse, HandshakeStep, KeyExchangeRequest,
+        KeyExchangeResponse,
+    },
+    identity::sign_key_exchange,
+    pake::{Side, Spake2},
+    role::Role,
+    session::SessionId,
+    EncryptedMessage,
+};
 use std::net::SocketAddr;
 use tower_http::trace::{self, TraceLayer};
 use tracing::Level;
-use x25519_dalek::{EphemeralSecret, PublicKey};
 
 pub fn routes_handshake(state: SharedState) -> Router {
     Router::new()
-        .route("/handshake/:id", put(handshake))
+        .route(endpoint::HANDSHAKE, put(handshake))
         .with_state(state)
         .layer(
             TraceLayer::new_for_http()
@@ -26,52 +34,196 @@ pub fn routes_handshake(state: SharedState) -> Router {
         )
 }
 
+/// the outcome of a handshake step: the response body and, once authenticated, the account's name,
+/// or the account's name (if known) and the reason the step failed
+type StepResult = Result<(Bytes, Option<String>), (Option<String>, &'static str)>;
+
 async fn handshake(
     State(state): State<SharedState>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Path(id): Path<u8>,
+    session: Option<Session>,
     body: Bytes,
 ) -> impl IntoResponse {
-    if id == 0 {
-        let mut client_public_key: [u8; 32] = [0; 32];
-        if body.len() != 32 {
-            return Err(Error::HandshakeFailed);
+    let step = HandshakeStep::try_from(id);
+    if let Ok(HandshakeStep::KeyExchange) = step {
+        throttle(&state, addr).await?;
+    }
+
+    let result = match (step, session) {
+        (Ok(HandshakeStep::KeyExchange), _) => key_exchange(&state, addr, &body).await,
+        (Ok(HandshakeStep::Authentication), Some(Session(session))) => {
+            authentication(&state, addr, session, &body).await
+        }
+        (Ok(HandshakeStep::Authentication), None) => Err((None, "missing session id")),
+        (Err(..), _) => Err((None, "unknown handshake step")),
+    };
+
+    let audit = state.read().await.audit.clone();
+    match result {
+        Ok((body, None)) => Ok(body),
+        Ok((body, Some(username))) => {
+            state.write().await.throttle.succeed(addr.ip());
+            audit
+                .record(addr.ip(), Some(username), AuditEvent::Authenticated)
+                .await;
+            Ok(body)
         }
-        for (idx, byte) in body.into_iter().enumerate() {
-            client_public_key[idx] = byte;
+        Err((username, reason)) => {
+            tracing::warn!(
+                "handshake with {:?} failed, {} ({:?})",
+                &addr.ip(),
+                reason,
+                username
+            );
+            audit
+                .record(
+                    addr.ip(),
+                    username,
+                    AuditEvent::HandshakeFailed {
+                        reason: reason.to_string(),
+                    },
+                )
+                .await;
+            Err(Error::HandshakeFailed)
         }
-        let client_public_key = PublicKey::from(client_public_key);
-        let server_private_key = EphemeralSecret::random_from_rng(StdRng::from_entropy());
-        let server_public_key = PublicKey::from(&server_private_key);
+    }
+}
 
-        state.write().await.add_client(
-            addr.ip(),
-            server_private_key
-                .diffie_hellman(&client_public_key)
-                .as_bytes(),
+/// refuses the key exchange if the address has to wait or the host has too many pending sessions,
+/// otherwise counts it as a failure until the client authenticates
+async fn throttle(state: &SharedState, addr: SocketAddr) -> Result<(), Error> {
+    let mut write_state = state.write().await;
+    write_state.update();
+
+    if let Err(wait) = write_state.throttle.check(addr.ip()) {
+        tracing::debug!(
+            "key exchange from {:?} refused, {} seconds left to wait",
+            &addr.ip(),
+            wait
         );
-        return Ok(Bytes::copy_from_slice(server_public_key.as_bytes()));
+        return Err(Error::TooManyRequests);
     }
-    if id == 1 {
-        let mut valid: bool = false;
-        if let Some(client_data) = state.read().await.db.get(&addr.ip()) {
-            if let Ok(encrypted_message) = EncryptedMessage::try_from_bytes(&body) {
-                if let Ok(decrypted_password_provided_by_client) =
-                    encrypted_message.try_decrypt(&client_data.cipher)
-                {
-                    if decrypted_password_provided_by_client == state.read().await.config.password {
-                        tracing::info!("{:?} authenticated", &addr.ip());
-                        valid = true;
-                    }
-                }
-            }
-        }
-        if valid {
-            if let Some(client_data) = state.write().await.db.get_mut(&addr.ip()) {
-                client_data.authenticate();
-                return Ok(Bytes::new());
-            }
+    if write_state.pending_sessions() >= write_state.config.max_pending_sessions {
+        tracing::warn!(
+            "key exchange from {:?} refused, too many pending sessions",
+            &addr.ip()
+        );
+        return Err(Error::TooManyRequests);
+    }
+
+    let (max_failures, ban_duration) = (
+        write_state.config.max_handshake_failures,
+        write_state.config.ban_duration,
+    );
+    write_state
+        .throttle
+        .fail(addr.ip(), max_failures, ban_duration);
+    Ok(())
+}
+
+async fn key_exchange(state: &SharedState, addr: SocketAddr, body: &[u8]) -> StepResult {
+    let request: KeyExchangeRequest =
+        bincode::deserialize(body).map_err(|_| (None, "invalid key exchange request"))?;
+
+    let (account, audit) = {
+        let read_state = state.read().await;
+        (
+            read_state.config.accounts.get(&request.username).cloned(),
+            read_state.audit.clone(),
+        )
+    };
+    // an unknown user goes through the same exchange with a random password, so that it cannot be told apart from
+    // a known user with a wrong password, the session it is given can never be authenticated
+    let known = account.is_some();
+    let account = account.unwrap_or_else(|| Account::generate(Role::Viewer));
+    let spake2 = Spake2::start(Side::Host, &request.username, &account.password);
+    let message = spake2.message();
+    let keys = match spake2.finish(&request.message) {
+        Ok(keys) => keys,
+        Err(..) => return Err((Some(request.username), "invalid key exchange message")),
+    };
+    if !known {
+        tracing::warn!(
+            "key exchange from {:?} as an unknown user ({:?})",
+            &addr.ip(),
+            request.username
+        );
+        // recorded in the background, the response should not take any longer than for a known user
+        let username = request.username.clone();
+        tokio::spawn(async move {
+            audit
+                .record(
+                    addr.ip(),
+                    Some(username),
+                    AuditEvent::HandshakeFailed {
+                        reason: String::from("unknown user"),
+                    },
+                )
+                .await;
+        });
+    }
+
+    let mut write_state = state.write().await;
+
+    let response = KeyExchangeResponse {
+        message,
+        confirmation: keys.host_confirmation(),
+        session: write_state.add_client(addr.ip(), request.username, account.role, &keys),
+        identity: write_state.identity.verifying_key(),
+        signature: sign_key_exchange(
+            write_state.identity.signing_key(),
+            &request.message,
+            &message,
+        ),
+    };
+    // unwrapping because this should never fail
+    Ok((Bytes::from(bincode::serialize(&response).unwrap()), None))
+}
+
+async fn authentication(
+    state: &SharedState,
+    addr: SocketAddr,
+    session: SessionId,
+    body: &[u8],
+) -> StepResult {
+    let mut write_state = state.write().await;
+    let client_data = write_state
+        .db
+        .get_mut(&session)
+        .ok_or((None, "unknown session id"))?;
+    // anyone who saw the session id could otherwise sign its client out with a wrong confirmation
+    if client_data.is_authenticated() {
+        return Err((
+            Some(client_data.username.clone()),
+            "session already authenticated",
+        ));
+    }
+
+    if let Ok(request) = bincode::deserialize::<AuthenticationRequest>(body) {
+        if client_data.authenticate(&request.confirmation) {
+            tracing::info!(
+                "{:?} authenticated as {} ({:?})",
+                &addr.ip(),
+                client_data.username,
+                client_data.role
+            );
+            let response = AuthenticationResponse {
+                role: client_data.role,
+            };
+            // unwrapping because this should never fail
+            let response =
+                EncryptedMessage::new(&bincode::serialize(&response).unwrap(), &client_data.cipher)
+                    .unwrap();
+            return Ok((
+                Bytes::from(response.into_bytes()),
+                Some(client_data.username.clone()),
+            ));
         }
     }
-    Err(Error::HandshakeFailed)
+
+    // a single guess per key exchange, the client has to start over
+    let username = client_data.username.clone();
+    write_state.db.remove(&session);
+    Err((Some(username), "wrong password"))
 }
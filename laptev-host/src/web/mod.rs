@@ -1,3 +1,5 @@
+pub mod admin;
 pub mod handler;
 pub mod handshake;
+pub mod session;
 pub mod status;
@@ -0,0 +1,25 @@
+use crate::error::Error;
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use laptev_protocol::session::{SessionId, SESSION_HEADER};
+use std::str::FromStr;
+
+/// extracts the session id sent by the client in `SESSION_HEADER`
+pub struct Session(pub SessionId);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Session
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .headers
+            .get(SESSION_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| SessionId::from_str(value).ok())
+            .map(Session)
+            .ok_or(Error::NotAuthenticated)
+    }
+}
@@ -1,18 +1,21 @@
-use crate::{data::internal::SharedState, error::Error};
+use crate::{data::internal::SharedState, error::Error, web::session::Session};
 use axum::{
-    extract::{ConnectInfo, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::get,
-    Router,
+    body::Bytes, extract::State, http::StatusCode, response::IntoResponse, routing::get, Router,
 };
-use std::net::SocketAddr;
+use laptev_protocol::{endpoint, EncryptedMessage};
 use tower_http::trace::{self, TraceLayer};
 use tracing::Level;
 
 pub fn routes_status(state: SharedState) -> Router {
     Router::new()
-        .route("/status", get(status))
+        .route(endpoint::STATUS, get(status))
+        .with_state(state.clone())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
+                .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .route(endpoint::CAPTURE, get(capture))
         .with_state(state.clone())
         .layer(
             TraceLayer::new_for_http()
@@ -22,17 +25,19 @@ pub fn routes_status(state: SharedState) -> Router {
 }
 
 /// returns OK only if the client is authenticated, otherwise returns FORBIDDEN
-async fn status(
-    State(state): State<SharedState>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
-) -> impl IntoResponse {
-    if let Some(client) = state.read().await.db.get(&addr.ip()) {
-        if !client.is_authenticated() {
-            return Err(Error::NotAuthenticated);
-        }
-    } else {
-        return Err(Error::NotAuthenticated);
-    }
+async fn status(State(state): State<SharedState>, Session(session): Session) -> impl IntoResponse {
+    state.read().await.get_authenticated(&session)?;
+
+    Ok::<_, Error>(StatusCode::OK)
+}
+
+/// returns the state of the capture process to any authenticated client
+async fn capture(State(state): State<SharedState>, Session(session): Session) -> impl IntoResponse {
+    let read_state = state.read().await;
+    let cipher = &read_state.get_authenticated(&session)?.cipher;
+    let body = read_state.capture.status();
 
-    Ok(StatusCode::OK)
+    // unwrapping because this should never fail
+    let response = EncryptedMessage::new(&bincode::serialize(&body).unwrap(), cipher).unwrap();
+    Ok::<_, Error>(Bytes::from(response.into_bytes()))
 }
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+use crate::admin::ReconcileAction;
+
+/// what a request changing something on the host does, it has to match the request it is sent with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Action {
+    // `endpoint::DELETE`
+    Delete { id: u64 },
+    // PUT (pinned) or DELETE (unpinned) on `endpoint::PIN`
+    Pin { id: u64, pinned: bool },
+    // POST on `endpoint::RESTORE`
+    Restore { id: u64 },
+    // POST on `endpoint::RECONCILE`
+    Reconcile { action: ReconcileAction },
+}
+
+/// the body of every request changing something on the host, inside an `EncryptedMessage`
+///
+/// the session id travels in the clear in `SESSION_HEADER`, sealing the action with the session's cipher proves
+/// the request comes from the client that holds its key, and the sequence number, which the client never reuses
+/// within a session, that it is not a replay of an earlier request
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct ActionRequest {
+    pub sequence: u64,
+    pub action: Action,
+}
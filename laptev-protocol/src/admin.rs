@@ -0,0 +1,79 @@
+//! the bodies of the administration endpoints, all restricted to `Role::Admin`
+
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// an address currently throttled or banned from the handshake endpoints
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BanEntry {
+    pub addr: IpAddr,
+    // key exchanges that did not end in a successful authentication
+    pub failures: u32,
+    // the timestamp before which the address may not attempt a new key exchange
+    pub retry_after: u64,
+    // whether the address reached the maximum amount of failures
+    pub banned: bool,
+}
+
+/// the decrypted body of a `endpoint::BANS` response
+pub type BansResponse = Vec<BanEntry>;
+
+/// what `endpoint::RECONCILE` does about the inconsistencies it finds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReconcileAction {
+    // only reports them
+    #[default]
+    Report,
+    // moves every file at fault to the quarantine subdirectory of its directory, to be inspected by hand
+    Quarantine,
+    // repairs what can be repaired, and quarantines the rest
+    Repair,
+}
+
+/// query parameters of `endpoint::RECONCILE`, only POST requests act on what they find
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct ReconcileRequest {
+    #[serde(default)]
+    pub action: ReconcileAction,
+}
+
+/// a file of the data directories that is not part of a complete event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Inconsistency {
+    // a thumbnail whose event has no clip, nor one being recorded
+    OrphanedThumbnail,
+    // a clip whose event has no thumbnail
+    OrphanedClip,
+    EmptyThumbnail,
+    EmptyClip,
+    // a clip whose recording stopped without being completed, e.g. the capture process was killed
+    StaleRecording,
+    // a file that is not part of any event
+    UnknownFile,
+}
+
+/// what became of an inconsistency
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    Reported,
+    Quarantined,
+    Repaired,
+    // the file was left as it was
+    Failed,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Finding {
+    // the file at fault, as laptev-host sees it, e.g. "./data/1700000000.jpg"
+    pub path: String,
+    // the event the file belongs to, None for unknown files
+    pub timestamp: Option<u64>,
+    pub inconsistency: Inconsistency,
+    pub resolution: Resolution,
+}
+
+/// the decrypted body of a `endpoint::RECONCILE` response, in the order the files were found
+pub type ReconcileResponse = Vec<Finding>;
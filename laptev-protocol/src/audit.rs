@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+use crate::admin::ReconcileAction;
+
+/// a single line of the host's append-only audit log
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AuditRecord {
+    pub timestamp: u64,
+    pub addr: IpAddr,
+    // the account involved, if known
+    pub username: Option<String>,
+    pub event: AuditEvent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEvent {
+    Authenticated,
+    HandshakeFailed {
+        reason: String,
+    },
+    Download {
+        id: u64,
+        success: bool,
+    },
+    Delete {
+        id: u64,
+        success: bool,
+    },
+    Pin {
+        id: u64,
+        pinned: bool,
+    },
+    Restore {
+        id: u64,
+        success: bool,
+    },
+    // how many inconsistencies of the data directories were acted on
+    Reconcile {
+        action: ReconcileAction,
+        findings: usize,
+    },
+}
+
+impl AuditEvent {
+    /// the entry this event concerns, if any
+    pub fn id(&self) -> Option<u64> {
+        match self {
+            Self::Download { id, .. }
+            | Self::Delete { id, .. }
+            | Self::Pin { id, .. }
+            | Self::Restore { id, .. } => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+/// query parameters of `endpoint::AUDIT`, every filter is optional
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct AuditRequest {
+    // only records at or after this timestamp
+    pub since: Option<u64>,
+    // only records at or before this timestamp
+    pub until: Option<u64>,
+    // only records concerning this entry
+    pub id: Option<u64>,
+    // the maximum amount of records to send back
+    pub limit: Option<usize>,
+}
+
+impl AuditRequest {
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        self.since.is_none_or(|since| record.timestamp >= since)
+            && self.until.is_none_or(|until| record.timestamp <= until)
+            && self.id.is_none_or(|id| record.event.id() == Some(id))
+    }
+}
+
+/// the decrypted body of a `endpoint::AUDIT` response, most recent records first
+pub type AuditResponse = Vec<AuditRecord>;
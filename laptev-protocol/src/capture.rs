@@ -0,0 +1,27 @@
+//! the state of the capture process (motioncapture.py) supervised by laptev-host
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum CaptureState {
+    // no capture command is configured, motion is captured by something else (or not at all)
+    Disabled,
+    Running,
+    // the process exited and will be started again at `CaptureStatus::restart_at`
+    Restarting,
+}
+
+/// the decrypted body of a `endpoint::CAPTURE` response
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CaptureStatus {
+    pub state: CaptureState,
+    pub pid: Option<u32>,
+    // seconds since the process was started, None unless it is running
+    pub uptime: Option<u64>,
+    // how many times the process was restarted since the host was launched
+    pub restarts: u32,
+    // the exit code of the previous process, None if there was none or it was killed by a signal
+    pub last_exit_code: Option<i32>,
+    // the timestamp at which the process will be started again, None unless it is restarting
+    pub restart_at: Option<u64>,
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+/// what became of one file of a deleted event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum DeletedFile {
+    // the event had no such file, e.g. a thumbnail whose clip was lost
+    Missing,
+    // moved to the trash, it can be restored through `endpoint::RESTORE` until it is purged
+    Trashed,
+    // removed for good
+    Removed,
+}
+
+/// the decrypted body of a `endpoint::DELETE` response,
+/// an event is deleted as a whole, either every file it had is gone or none is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DeleteResponse {
+    pub thumbnail: DeletedFile,
+    pub clip: DeletedFile,
+}
+
+impl DeleteResponse {
+    /// whether the event can still be restored
+    pub fn trashed(&self) -> bool {
+        self.thumbnail == DeletedFile::Trashed || self.clip == DeletedFile::Trashed
+    }
+}
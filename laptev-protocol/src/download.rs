@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+use crate::stream::CHUNK_SIZE;
+
+/// query parameters of `endpoint::DOWNLOAD`
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct DownloadRequest {
+    // where to resume the download from, in bytes of the clip,
+    // it has to be a multiple of `stream::CHUNK_SIZE` as chunks are sealed as a whole
+    #[serde(default)]
+    pub offset: u64,
+}
+
+impl DownloadRequest {
+    /// the index of the first chunk to send, None if the offset does not fall on a chunk boundary
+    pub fn first_chunk(&self) -> Option<u32> {
+        if !self.offset.is_multiple_of(CHUNK_SIZE as u64) {
+            return None;
+        }
+        u32::try_from(self.offset / CHUNK_SIZE as u64).ok()
+    }
+}
@@ -0,0 +1,48 @@
+//! the paths served by laptev-host, as route patterns (for the host's router)
+//! and as helpers building concrete paths (for clients)
+
+use crate::handshake::HandshakeStep;
+
+pub const STATUS: &str = "/status";
+pub const CAPTURE: &str = "/status/capture";
+pub const HANDSHAKE: &str = "/handshake/:id";
+pub const SYNCHRONIZE: &str = "/synchronize";
+pub const LIST: &str = "/list";
+pub const THUMBNAIL: &str = "/thumbnail/:id";
+pub const DOWNLOAD: &str = "/download/:id";
+// its body, like that of PIN and of POST on RESTORE and RECONCILE, is a sealed `action::ActionRequest`
+pub const DELETE: &str = "/delete/:id";
+// PUT pins the event, DELETE unpins it
+pub const PIN: &str = "/pin/:id";
+// GET lists the deleted events, POST on one of them puts it back
+pub const TRASH: &str = "/trash";
+pub const RESTORE: &str = "/trash/:id";
+pub const NOTIFICATIONS: &str = "/notifications";
+pub const AUDIT: &str = "/admin/audit";
+pub const BANS: &str = "/admin/bans";
+// GET reports the inconsistencies of the data directories, POST also acts on them
+pub const RECONCILE: &str = "/admin/reconcile";
+
+pub fn handshake(step: HandshakeStep) -> String {
+    format!("/handshake/{}", step as u8)
+}
+
+pub fn thumbnail(id: u64) -> String {
+    format!("/thumbnail/{}", id)
+}
+
+pub fn download(id: u64) -> String {
+    format!("/download/{}", id)
+}
+
+pub fn delete(id: u64) -> String {
+    format!("/delete/{}", id)
+}
+
+pub fn pin(id: u64) -> String {
+    format!("/pin/{}", id)
+}
+
+pub fn restore(id: u64) -> String {
+    format!("/trash/{}", id)
+}
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    identity::{Signature, VerifyingKey},
+    pake::{Confirmation, PakeMessage},
+    role::Role,
+    session::SessionId,
+};
+
+/// the steps of the handshake, each one is a `PUT` on `endpoint::handshake(step)` with a bincode body,
+/// together they form a SPAKE2 exchange (see `pake`), the password itself is never sent
+///
+/// 1. `KeyExchange`: the client sends a `KeyExchangeRequest`, the host answers with a `KeyExchangeResponse`
+///    proving it knows the password, signed with its long-term identity (see `identity`)
+/// 2. `Authentication`: the client checks the host's confirmation, then sends an `AuthenticationRequest`
+///    proving it knows the password too, along with the session id it was issued in `SESSION_HEADER`,
+///    the host answers with an `AuthenticationResponse` inside an `EncryptedMessage`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum HandshakeStep {
+    KeyExchange = 0,
+    Authentication = 1,
+}
+
+impl TryFrom<u8> for HandshakeStep {
+    type Error = u8;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::KeyExchange),
+            1 => Ok(Self::Authentication),
+            other => Err(other),
+        }
+    }
+}
+
+/// the client's half of the SPAKE2 exchange
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct KeyExchangeRequest {
+    // the account the client wants to authenticate as
+    pub username: String,
+    pub message: PakeMessage,
+}
+
+/// the host's answer to `HandshakeStep::KeyExchange`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct KeyExchangeResponse {
+    // the host's half of the SPAKE2 exchange
+    pub message: PakeMessage,
+    pub confirmation: Confirmation,
+    // the session id that will identify the client once authenticated
+    pub session: SessionId,
+    // the host's long-term identity, and its signature over both halves of the exchange
+    pub identity: VerifyingKey,
+    pub signature: Signature,
+}
+
+/// the client's proof that it derived the same keys as the host
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AuthenticationRequest {
+    pub confirmation: Confirmation,
+}
+
+/// what the authenticated account is allowed to do
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AuthenticationResponse {
+    pub role: Role,
+}
@@ -0,0 +1,84 @@
+//! the host's long-term Ed25519 identity
+//!
+//! the host signs every key exchange it answers, clients pin the host's verifying key
+//! on first use and refuse to talk to a host presenting a different one afterwards
+
+use anyhow::Result;
+pub use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
+use ed25519_dalek::{Signer, Verifier};
+use sha2::{Digest, Sha256};
+
+use crate::pake::PakeMessage;
+
+/// signs both halves of a SPAKE2 exchange, binding the host's identity to this exchange only
+pub fn sign_key_exchange(
+    key: &SigningKey,
+    client_message: &PakeMessage,
+    host_message: &PakeMessage,
+) -> Signature {
+    key.sign(&key_exchange_transcript(client_message, host_message))
+}
+
+pub fn verify_key_exchange(
+    key: &VerifyingKey,
+    client_message: &PakeMessage,
+    host_message: &PakeMessage,
+    signature: &Signature,
+) -> Result<()> {
+    Ok(key.verify(
+        &key_exchange_transcript(client_message, host_message),
+        signature,
+    )?)
+}
+
+/// a lowercase hexadecimal representation of the verifying key, used to pin and display identities
+pub fn fingerprint(key: &VerifyingKey) -> String {
+    key.as_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// the lowercase hexadecimal SHA-256 of a DER encoded TLS certificate, used to pin the host's certificate
+pub fn certificate_fingerprint(certificate: &[u8]) -> String {
+    Sha256::digest(certificate)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn key_exchange_transcript(client_message: &PakeMessage, host_message: &PakeMessage) -> Vec<u8> {
+    [
+        b"laptev identity".as_slice(),
+        client_message.as_slice(),
+        host_message.as_slice(),
+    ]
+    .concat()
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn key_exchange_signature() {
+        use super::{sign_key_exchange, verify_key_exchange, SigningKey};
+        use rand::SeedableRng;
+
+        let key = SigningKey::generate(&mut rand::rngs::StdRng::from_entropy());
+        let (client_message, host_message) = ([1; 32], [2; 32]);
+        let signature = sign_key_exchange(&key, &client_message, &host_message);
+        assert!(verify_key_exchange(
+            &key.verifying_key(),
+            &client_message,
+            &host_message,
+            &signature
+        )
+        .is_ok());
+        assert!(verify_key_exchange(
+            &key.verifying_key(),
+            &host_message,
+            &client_message,
+            &signature
+        )
+        .is_err());
+    }
+}
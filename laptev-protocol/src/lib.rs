@@ -0,0 +1,26 @@
+//! wire types shared by laptev-host and laptev-client
+//!
+//! every request and response body, the handshake steps and the endpoint paths live here,
+//! so that both sides (and third-party tools) speak exactly the same protocol
+
+pub mod action;
+pub mod admin;
+pub mod audit;
+pub mod capture;
+pub mod delete;
+pub mod download;
+pub mod endpoint;
+pub mod handshake;
+pub mod identity;
+pub mod list;
+pub mod message;
+pub mod notification;
+pub mod pake;
+pub mod password;
+pub mod role;
+pub mod session;
+pub mod stream;
+pub mod sync;
+pub mod trash;
+
+pub use message::EncryptedMessage;
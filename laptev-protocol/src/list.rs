@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use crate::sync::SyncRequest;
+
+/// query parameters of `endpoint::LIST`, paginated exactly like `endpoint::SYNCHRONIZE`
+pub type ListRequest = SyncRequest;
+
+/// what is known about a motion event, without its thumbnail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EventMetadata {
+    pub timestamp: u64,
+    // the size of the thumbnail in bytes, None if there is none
+    pub thumbnail_size: Option<u64>,
+    // the size of the clip in bytes, None if there is none
+    pub clip_size: Option<u64>,
+    // the duration of the clip in milliseconds, None if there is no clip or it could not be read
+    pub duration: Option<u64>,
+    // the clip is still being recorded, it cannot be downloaded yet
+    pub recording: bool,
+    // the event is kept whatever its age and the storage budget of the host, until it is unpinned
+    pub pinned: bool,
+}
+
+/// the decrypted body of a `endpoint::LIST` response
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ListResponse {
+    // most recent events first
+    pub events: Vec<EventMetadata>,
+    // the `before` cursor of the page of older events, None if there are none
+    pub older: Option<u64>,
+    // the `after` cursor of the page of newer events, None if there are none
+    pub newer: Option<u64>,
+}
@@ -1,9 +1,9 @@
 use aes_gcm_siv::{aead::Aead, Aes256GcmSiv};
 use anyhow::Result;
+use rand::{RngCore, SeedableRng};
 use serde::{Deserialize, Serialize};
 
-use crate::utils::rng_fill_bytes;
-
+/// the envelope used for every encrypted body, serialized with bincode
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct EncryptedMessage {
     nonce: [u8; 12],
@@ -13,7 +13,7 @@ pub struct EncryptedMessage {
 impl EncryptedMessage {
     pub fn new(unencrypted_data: &[u8], cipher: &Aes256GcmSiv) -> Result<Self> {
         let mut nonce: [u8; 12] = [0; 12];
-        rng_fill_bytes(&mut nonce);
+        rand::rngs::StdRng::from_entropy().fill_bytes(&mut nonce);
         Ok(Self {
             nonce,
             data: cipher.encrypt(&nonce.into(), unencrypted_data)?,
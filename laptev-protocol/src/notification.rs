@@ -0,0 +1,83 @@
+//! the changes pushed to clients over `endpoint::NOTIFICATIONS`, a server-sent event stream
+//!
+//! the `data` field of every server-sent event holds a single bincode serialized `Notification`,
+//! encrypted like any other body then hex encoded, as server-sent events can only carry text
+
+use aes_gcm_siv::Aes256GcmSiv;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{list::EventMetadata, EncryptedMessage};
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Notification {
+    // motion was detected, its clip is usually still being recorded
+    New(EventMetadata),
+    // the clip of an event was completed and can be downloaded
+    Completed(EventMetadata),
+    // the files of an event were removed, by a client or because they expired
+    Deleted(u64),
+    // something other than the files of an event changed, e.g. it was pinned
+    Updated(EventMetadata),
+}
+
+impl Notification {
+    /// the `data` field of the server-sent event carrying this notification
+    pub fn seal(&self, cipher: &Aes256GcmSiv) -> Result<String> {
+        let message = EncryptedMessage::new(&bincode::serialize(self)?, cipher)?;
+        Ok(message
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect())
+    }
+
+    /// the notification carried by the `data` field of a server-sent event
+    pub fn open(data: &str, cipher: &Aes256GcmSiv) -> Result<Self> {
+        let data = data.trim();
+        // also keeps the slicing below on character boundaries
+        if !data.is_ascii() || !data.len().is_multiple_of(2) {
+            anyhow::bail!("invalid hexadecimal data");
+        }
+        let bytes = (0..data.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&data[index..index + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()?;
+        let message = EncryptedMessage::try_from_bytes(&bytes)?;
+        Ok(bincode::deserialize(&message.try_decrypt(cipher)?)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Notification;
+    use crate::list::EventMetadata;
+    use aes_gcm_siv::{Aes256GcmSiv, KeyInit};
+    use rand::SeedableRng;
+
+    #[test]
+    fn notification() {
+        let cipher: Aes256GcmSiv = Aes256GcmSiv::new(&Aes256GcmSiv::generate_key(
+            rand::rngs::StdRng::from_entropy(),
+        ));
+        let notifications = [
+            Notification::New(EventMetadata {
+                timestamp: 1700000000,
+                thumbnail_size: Some(2048),
+                clip_size: None,
+                duration: None,
+                recording: true,
+                pinned: false,
+            }),
+            Notification::Deleted(1700000000),
+        ];
+        for notification in notifications {
+            let data = notification.seal(&cipher).unwrap();
+            assert!(data.is_ascii() && !data.contains('\n'));
+            assert_eq!(notification, Notification::open(&data, &cipher).unwrap());
+        }
+        assert!(Notification::open("abc", &cipher).is_err());
+        assert!(Notification::open("zz", &cipher).is_err());
+        assert!(Notification::open("aéb", &cipher).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+//! SPAKE2 (RFC 9382) over the ristretto255 group
+//!
+//! both the client and the host derive the session key from the shared password,
+//! without the password (or anything an eavesdropper could test guesses against) ever crossing the wire,
+//! the exchanged confirmations then prove to each side that the other one knows the password
+
+use aes_gcm_siv::{Aes256GcmSiv, KeyInit};
+use anyhow::Result;
+use curve25519_dalek::{
+    constants::RISTRETTO_BASEPOINT_POINT,
+    ristretto::{CompressedRistretto, RistrettoPoint},
+    scalar::Scalar,
+};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use subtle::ConstantTimeEq;
+
+/// the length of a serialized SPAKE2 message, a compressed ristretto point
+pub const PAKE_MESSAGE_LENGTH: usize = 32;
+
+pub type PakeMessage = [u8; PAKE_MESSAGE_LENGTH];
+
+/// which side of the exchange we are, the client is `A` and the host is `B` in RFC 9382
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Client,
+    Host,
+}
+
+pub struct Spake2 {
+    side: Side,
+    username: Vec<u8>,
+    secret: Scalar,
+    password: Scalar,
+    message: PakeMessage,
+}
+
+impl Spake2 {
+    /// the username is bound to the exchange, as the client's identity `A` in RFC 9382
+    pub fn start(side: Side, username: &str, password: &[u8]) -> Self {
+        let secret = Scalar::random(&mut rand::rngs::StdRng::from_entropy());
+        let password = password_to_scalar(password);
+        let blinding = match side {
+            Side::Client => point_m(),
+            Side::Host => point_n(),
+        };
+        let message = (RISTRETTO_BASEPOINT_POINT * secret + blinding * password)
+            .compress()
+            .to_bytes();
+        Self {
+            side,
+            username: username.as_bytes().to_vec(),
+            secret,
+            password,
+            message,
+        }
+    }
+
+    /// the message to send to the other side
+    pub fn message(&self) -> PakeMessage {
+        self.message
+    }
+
+    /// derives the session keys from the other side's message
+    pub fn finish(self, peer_message: &[u8]) -> Result<SessionKeys> {
+        let peer_point = CompressedRistretto::from_slice(peer_message)?
+            .decompress()
+            .ok_or(anyhow::anyhow!("the peer's message is not a valid point"))?;
+        if peer_message == self.message {
+            anyhow::bail!("the peer reflected our own message");
+        }
+        let peer_blinding = match self.side {
+            Side::Client => point_n(),
+            Side::Host => point_m(),
+        };
+        let shared_point = (peer_point - peer_blinding * self.password) * self.secret;
+
+        let (client_message, host_message): (&[u8], &[u8]) = match self.side {
+            Side::Client => (&self.message, peer_message),
+            Side::Host => (peer_message, &self.message),
+        };
+        let mut transcript: Vec<u8> = Vec::with_capacity(256);
+        for element in [
+            self.username.as_slice(),
+            b"laptev-host".as_slice(),
+            client_message,
+            host_message,
+            shared_point.compress().as_bytes(),
+            self.password.as_bytes(),
+        ] {
+            transcript.extend_from_slice(&(element.len() as u64).to_le_bytes());
+            transcript.extend_from_slice(element);
+        }
+
+        let hash = Sha512::digest(&transcript);
+        let (encryption_key, authentication_key) = hash.split_at(32);
+
+        let mut confirmation_keys: [u8; 64] = [0; 64];
+        // unwrap because 64 bytes is a valid length for HKDF-SHA256
+        Hkdf::<Sha256>::new(None, authentication_key)
+            .expand(b"ConfirmationKeys", &mut confirmation_keys)
+            .unwrap();
+        let (client_confirmation_key, host_confirmation_key) = confirmation_keys.split_at(32);
+
+        Ok(SessionKeys {
+            // unwrap because our key is guaranteed to be 32 bytes long
+            encryption_key: encryption_key.try_into().unwrap(),
+            client_confirmation: Confirmation::compute(client_confirmation_key, &transcript),
+            host_confirmation: Confirmation::compute(host_confirmation_key, &transcript),
+        })
+    }
+}
+
+/// the keys both sides end up with after a successful exchange
+pub struct SessionKeys {
+    encryption_key: [u8; 32],
+    client_confirmation: Confirmation,
+    host_confirmation: Confirmation,
+}
+
+impl SessionKeys {
+    pub fn cipher(&self) -> Aes256GcmSiv {
+        // unwrap because our key is guaranteed to be 32 bytes long
+        Aes256GcmSiv::new_from_slice(&self.encryption_key).unwrap()
+    }
+    /// the confirmation sent by the client, checked by the host
+    pub fn client_confirmation(&self) -> Confirmation {
+        self.client_confirmation
+    }
+    /// the confirmation sent by the host, checked by the client
+    pub fn host_confirmation(&self) -> Confirmation {
+        self.host_confirmation
+    }
+}
+
+/// a MAC over the transcript proving knowledge of the password, compared in constant time
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct Confirmation([u8; 32]);
+
+impl Confirmation {
+    fn compute(key: &[u8], transcript: &[u8]) -> Self {
+        // unwrap because HMAC accepts keys of any length
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap();
+        mac.update(transcript);
+        Self(mac.finalize().into_bytes().into())
+    }
+}
+
+impl PartialEq for Confirmation {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ct_eq(&other.0).into()
+    }
+}
+
+impl Eq for Confirmation {}
+
+fn password_to_scalar(password: &[u8]) -> Scalar {
+    Scalar::hash_from_bytes::<Sha512>(&[b"laptev SPAKE2 password".as_slice(), password].concat())
+}
+
+fn point_m() -> RistrettoPoint {
+    RistrettoPoint::hash_from_bytes::<Sha512>(b"laptev SPAKE2 M")
+}
+
+fn point_n() -> RistrettoPoint {
+    RistrettoPoint::hash_from_bytes::<Sha512>(b"laptev SPAKE2 N")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Side, Spake2};
+
+    #[test]
+    fn matching_passwords() {
+        let client = Spake2::start(Side::Client, "admin", b"password");
+        let host = Spake2::start(Side::Host, "admin", b"password");
+        let (client_message, host_message) = (client.message(), host.message());
+
+        let client_keys = client.finish(&host_message).unwrap();
+        let host_keys = host.finish(&client_message).unwrap();
+        assert_eq!(client_keys.encryption_key, host_keys.encryption_key);
+        assert_eq!(
+            client_keys.client_confirmation(),
+            host_keys.client_confirmation()
+        );
+        assert_eq!(
+            client_keys.host_confirmation(),
+            host_keys.host_confirmation()
+        );
+    }
+
+    #[test]
+    fn mismatched_passwords() {
+        let client = Spake2::start(Side::Client, "admin", b"password");
+        let host = Spake2::start(Side::Host, "admin", b"drowssap");
+        let (client_message, host_message) = (client.message(), host.message());
+
+        let client_keys = client.finish(&host_message).unwrap();
+        let host_keys = host.finish(&client_message).unwrap();
+        assert_ne!(client_keys.encryption_key, host_keys.encryption_key);
+        assert_ne!(
+            client_keys.client_confirmation(),
+            host_keys.client_confirmation()
+        );
+    }
+
+    #[test]
+    fn mismatched_usernames() {
+        let client = Spake2::start(Side::Client, "admin", b"password");
+        let host = Spake2::start(Side::Host, "viewer", b"password");
+        let (client_message, host_message) = (client.message(), host.message());
+
+        let client_keys = client.finish(&host_message).unwrap();
+        let host_keys = host.finish(&client_message).unwrap();
+        assert_ne!(client_keys.encryption_key, host_keys.encryption_key);
+    }
+
+    #[test]
+    fn reflected_message() {
+        let host = Spake2::start(Side::Host, "admin", b"password");
+        let host_message = host.message();
+        assert!(host.finish(&host_message).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+//! how account passwords are written in the configurations of the host and the client
+//!
+//! passwords are written as lowercase hexadecimal strings, short enough to be copied around by hand,
+//! the lists of numbers older configurations used are still accepted, use
+//! `#[serde(with = "laptev_protocol::password")]` on a `Vec<u8>` field
+
+use anyhow::Result;
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// e.g. "01d572a8..."
+pub fn encode(password: &[u8]) -> String {
+    password
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn decode(encoded: &str) -> Result<Vec<u8>> {
+    if !encoded.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        anyhow::bail!("a password is only made of hexadecimal digits");
+    }
+    if !encoded.len().is_multiple_of(2) {
+        anyhow::bail!("a password is an even amount of hexadecimal digits");
+    }
+    // only ASCII digits, so every index is a char boundary
+    Ok((0..encoded.len())
+        .step_by(2)
+        .filter_map(|index| u8::from_str_radix(&encoded[index..index + 2], 16).ok())
+        .collect())
+}
+
+pub fn serialize<S: Serializer>(password: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&encode(password))
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Encoded(String),
+        // written by older versions
+        Bytes(Vec<u8>),
+    }
+    match Repr::deserialize(deserializer)? {
+        Repr::Encoded(encoded) => decode(&encoded).map_err(serde::de::Error::custom),
+        Repr::Bytes(password) => Ok(password),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn password() {
+        use super::{decode, encode};
+
+        let password: Vec<u8> = (0..=255).collect();
+        let encoded = encode(&password);
+        assert_eq!(encoded.len(), 512);
+        assert!(encoded.starts_with("000102") && encoded.ends_with("fdfeff"));
+        assert_eq!(decode(&encoded).unwrap(), password);
+        assert_eq!(decode("01D5ff").unwrap(), vec![1, 213, 255]);
+        assert!(decode("").unwrap().is_empty());
+        assert!(decode("abc").is_err());
+        assert!(decode("0g").is_err());
+        assert!(decode("+1").is_err());
+        assert!(decode("éa").is_err());
+    }
+}
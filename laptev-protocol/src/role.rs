@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+/// what an account is allowed to do, each role grants its own permission and those of the roles below it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    // can browse thumbnails
+    Viewer = 0,
+    // can also download clips
+    Member = 1,
+    // can also delete clips
+    Moderator = 2,
+    // can do everything, including administration
+    Admin = 3,
+}
+
+/// the abilities checked by the host before answering a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    View = 0,
+    Download = 1,
+    Delete = 2,
+    Administer = 3,
+}
+
+impl Role {
+    pub fn allows(self, permission: Permission) -> bool {
+        self as u8 >= permission as u8
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn role_permissions() {
+        use super::{Permission, Role};
+
+        assert!(Role::Viewer.allows(Permission::View));
+        assert!(!Role::Viewer.allows(Permission::Download));
+        assert!(Role::Member.allows(Permission::Download));
+        assert!(!Role::Member.allows(Permission::Delete));
+        assert!(Role::Moderator.allows(Permission::Delete));
+        assert!(!Role::Moderator.allows(Permission::Administer));
+        assert!(Role::Admin.allows(Permission::Administer));
+    }
+}
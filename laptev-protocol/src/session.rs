@@ -0,0 +1,55 @@
+use rand::{RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// the header carrying the session id on every request made after `HandshakeStep::KeyExchange`
+pub const SESSION_HEADER: &str = "x-laptev-session";
+
+/// an opaque session id issued by the host during the key exchange,
+/// transmitted as a lowercase hexadecimal string in `SESSION_HEADER`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct SessionId([u8; 32]);
+
+impl SessionId {
+    pub fn generate() -> Self {
+        let mut bytes: [u8; 32] = [0; 32];
+        rand::rngs::StdRng::from_entropy().fill_bytes(&mut bytes);
+        Self(bytes)
+    }
+}
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SessionId {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 64 || !s.is_ascii() {
+            anyhow::bail!("a session id must be 64 hexadecimal characters long");
+        }
+        let mut bytes: [u8; 32] = [0; 32];
+        for (idx, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[idx * 2..idx * 2 + 2], 16)?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn session_id() {
+        use super::SessionId;
+        use std::str::FromStr;
+
+        let session = SessionId::generate();
+        assert_eq!(session, SessionId::from_str(&session.to_string()).unwrap());
+        assert!(SessionId::from_str("not a session id").is_err());
+    }
+}
@@ -0,0 +1,283 @@
+//! STREAM (Hoang, Reyhanitabar, Rogaway, Vizár) over AES-256-GCM-SIV, used for clip downloads
+//!
+//! the plaintext is split into chunks of `CHUNK_SIZE` bytes, each one sealed independently under the nonce
+//! `prefix (7 bytes) || counter (u32, big endian) || last (1 byte)`, so chunks cannot be reordered,
+//! dropped or duplicated, and a truncated stream is detected because its last chunk is never seen
+//!
+//! on the wire: `prefix || chunk 0 || chunk 1 || ... || last chunk`, every chunk but the last one
+//! holds exactly `CHUNK_SIZE` bytes of plaintext, the last one strictly less (possibly none),
+//! which is how the receiver knows where the stream ends
+//!
+//! a stream may also start at any chunk, to resume an interrupted download, it is then sealed under a fresh prefix
+//! and its counter starts at that chunk's index, the resumed plaintext simply follows what was already received
+//!
+//! every chunk is also bound to the id of the clip as associated data, the chunks of another clip sealed under
+//! the same session key fail to decrypt rather than being appended to the wrong partial download
+
+use aes_gcm_siv::{
+    aead::{Aead, Payload},
+    Aes256GcmSiv,
+};
+use anyhow::Result;
+use rand::{RngCore, SeedableRng};
+
+/// the amount of plaintext sealed in each chunk
+pub const CHUNK_SIZE: usize = 64 * 1024;
+/// the length of the AES-GCM-SIV tag appended to each chunk
+pub const TAG_LENGTH: usize = 16;
+/// the length of a full chunk once sealed
+pub const ENCRYPTED_CHUNK_SIZE: usize = CHUNK_SIZE + TAG_LENGTH;
+pub const NONCE_PREFIX_LENGTH: usize = 7;
+
+pub type NoncePrefix = [u8; NONCE_PREFIX_LENGTH];
+
+/// the length of the encrypted stream of a plaintext of the given length, prefix included
+pub fn encrypted_length(plaintext_length: u64) -> u64 {
+    let chunks = plaintext_length / CHUNK_SIZE as u64 + 1;
+    NONCE_PREFIX_LENGTH as u64 + plaintext_length + chunks * TAG_LENGTH as u64
+}
+
+fn nonce(prefix: &NoncePrefix, counter: u32, last: bool) -> [u8; 12] {
+    let mut nonce: [u8; 12] = [0; 12];
+    nonce[..NONCE_PREFIX_LENGTH].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LENGTH..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+pub struct StreamEncryptor {
+    prefix: NoncePrefix,
+    counter: u32,
+    // the id of the clip, as associated data
+    id: [u8; 8],
+}
+
+impl StreamEncryptor {
+    /// a stream of the clip with the given id
+    pub fn new(id: u64) -> Self {
+        Self::starting_at(id, 0)
+    }
+
+    /// a stream of the clip with the given id, whose first chunk is the given one of the plaintext
+    pub fn starting_at(id: u64, chunk: u32) -> Self {
+        let mut prefix: NoncePrefix = [0; NONCE_PREFIX_LENGTH];
+        rand::rngs::StdRng::from_entropy().fill_bytes(&mut prefix);
+        Self {
+            prefix,
+            counter: chunk,
+            id: id.to_be_bytes(),
+        }
+    }
+
+    /// the bytes to send before any chunk
+    pub fn header(&self) -> NoncePrefix {
+        self.prefix
+    }
+
+    /// seals a chunk that is not the last one, it has to be exactly `CHUNK_SIZE` bytes long
+    pub fn encrypt_next(&mut self, chunk: &[u8], cipher: &Aes256GcmSiv) -> Result<Vec<u8>> {
+        if chunk.len() != CHUNK_SIZE {
+            anyhow::bail!("only the last chunk may be shorter than CHUNK_SIZE");
+        }
+        let data = cipher.encrypt(
+            &nonce(&self.prefix, self.counter, false).into(),
+            Payload {
+                msg: chunk,
+                aad: &self.id,
+            },
+        )?;
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or(anyhow::anyhow!("too many chunks in a single stream"))?;
+        Ok(data)
+    }
+
+    /// seals the last chunk, it has to be shorter than `CHUNK_SIZE`, and may be empty
+    pub fn encrypt_last(self, chunk: &[u8], cipher: &Aes256GcmSiv) -> Result<Vec<u8>> {
+        if chunk.len() >= CHUNK_SIZE {
+            anyhow::bail!("the last chunk has to be shorter than CHUNK_SIZE");
+        }
+        Ok(cipher.encrypt(
+            &nonce(&self.prefix, self.counter, true).into(),
+            Payload {
+                msg: chunk,
+                aad: &self.id,
+            },
+        )?)
+    }
+}
+
+/// decrypts a stream as it is received, only ever holding a single chunk in memory
+pub struct StreamDecryptor {
+    prefix: Option<NoncePrefix>,
+    counter: u32,
+    // the id of the clip, as associated data
+    id: [u8; 8],
+    buffer: Vec<u8>,
+}
+
+impl StreamDecryptor {
+    /// decrypts a stream of the clip with the given id
+    pub fn new(id: u64) -> Self {
+        Self::starting_at(id, 0)
+    }
+
+    /// decrypts a stream of the clip with the given id, whose first chunk is the given one of the plaintext
+    pub fn starting_at(id: u64, chunk: u32) -> Self {
+        Self {
+            prefix: None,
+            counter: chunk,
+            id: id.to_be_bytes(),
+            buffer: Vec::new(),
+        }
+    }
+
+    /// feeds the received bytes, returns the plaintext of every chunk completed by them
+    pub fn push(&mut self, bytes: &[u8], cipher: &Aes256GcmSiv) -> Result<Vec<u8>> {
+        self.buffer.extend_from_slice(bytes);
+        if self.prefix.is_none() {
+            if self.buffer.len() < NONCE_PREFIX_LENGTH {
+                return Ok(Vec::new());
+            }
+            // unwrap because the slice is exactly NONCE_PREFIX_LENGTH bytes long
+            self.prefix = Some(self.buffer[..NONCE_PREFIX_LENGTH].try_into().unwrap());
+            self.buffer.drain(..NONCE_PREFIX_LENGTH);
+        }
+        // unwrap because the prefix was just set
+        let prefix = self.prefix.unwrap();
+
+        // a full chunk can never be the last one
+        let mut plaintext: Vec<u8> = Vec::new();
+        while self.buffer.len() >= ENCRYPTED_CHUNK_SIZE {
+            plaintext.extend(cipher.decrypt(
+                &nonce(&prefix, self.counter, false).into(),
+                Payload {
+                    msg: &self.buffer[..ENCRYPTED_CHUNK_SIZE],
+                    aad: &self.id,
+                },
+            )?);
+            self.buffer.drain(..ENCRYPTED_CHUNK_SIZE);
+            self.counter = self
+                .counter
+                .checked_add(1)
+                .ok_or(anyhow::anyhow!("too many chunks in a single stream"))?;
+        }
+        Ok(plaintext)
+    }
+
+    /// to call once the whole stream was received, decrypts the last chunk,
+    /// fails if the stream was truncated
+    pub fn finish(self, cipher: &Aes256GcmSiv) -> Result<Vec<u8>> {
+        let prefix = self
+            .prefix
+            .ok_or(anyhow::anyhow!("the stream ended before its header"))?;
+        Ok(cipher.decrypt(
+            &nonce(&prefix, self.counter, true).into(),
+            Payload {
+                msg: self.buffer.as_slice(),
+                aad: &self.id,
+            },
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        encrypted_length, StreamDecryptor, StreamEncryptor, CHUNK_SIZE, NONCE_PREFIX_LENGTH,
+        TAG_LENGTH,
+    };
+    use crate::download::DownloadRequest;
+    use aes_gcm_siv::{Aes256GcmSiv, KeyInit};
+    use rand::SeedableRng;
+
+    fn encrypt(plaintext: &[u8], id: u64, first_chunk: u32, cipher: &Aes256GcmSiv) -> Vec<u8> {
+        let mut encryptor = StreamEncryptor::starting_at(id, first_chunk);
+        let mut stream = encryptor.header().to_vec();
+        let mut chunks = plaintext.chunks_exact(CHUNK_SIZE);
+        for chunk in chunks.by_ref() {
+            stream.extend(encryptor.encrypt_next(chunk, cipher).unwrap());
+        }
+        stream.extend(encryptor.encrypt_last(chunks.remainder(), cipher).unwrap());
+        stream
+    }
+
+    #[test]
+    fn stream() {
+        let cipher: Aes256GcmSiv = Aes256GcmSiv::new(&Aes256GcmSiv::generate_key(
+            rand::rngs::StdRng::from_entropy(),
+        ));
+        for length in [0, 1, CHUNK_SIZE, 2 * CHUNK_SIZE + 5] {
+            let plaintext: Vec<u8> = (0..length).map(|index| index as u8).collect();
+            let stream = encrypt(&plaintext, 1700000000, 0, &cipher);
+            assert_eq!(stream.len() as u64, encrypted_length(length as u64));
+
+            // received in arbitrarily sized pieces
+            let mut decryptor = StreamDecryptor::new(1700000000);
+            let mut decrypted: Vec<u8> = Vec::new();
+            for piece in stream.chunks(1000) {
+                decrypted.extend(decryptor.push(piece, &cipher).unwrap());
+            }
+            decrypted.extend(decryptor.finish(&cipher).unwrap());
+            assert_eq!(plaintext, decrypted);
+
+            // truncated right after a full chunk
+            if length > CHUNK_SIZE {
+                let mut decryptor = StreamDecryptor::new(1700000000);
+                decryptor
+                    .push(&stream[..stream.len() - 21], &cipher)
+                    .unwrap();
+                assert!(decryptor.finish(&cipher).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn resumed_stream() {
+        let cipher: Aes256GcmSiv = Aes256GcmSiv::new(&Aes256GcmSiv::generate_key(
+            rand::rngs::StdRng::from_entropy(),
+        ));
+        let plaintext: Vec<u8> = (0..3 * CHUNK_SIZE + 7).map(|index| index as u8).collect();
+        let request = DownloadRequest {
+            offset: 2 * CHUNK_SIZE as u64,
+        };
+        let first_chunk = request.first_chunk().unwrap();
+        let stream = encrypt(
+            &plaintext[request.offset as usize..],
+            1700000000,
+            first_chunk,
+            &cipher,
+        );
+
+        let mut decryptor = StreamDecryptor::starting_at(1700000000, first_chunk);
+        let mut decrypted = decryptor.push(&stream, &cipher).unwrap();
+        decrypted.extend(decryptor.finish(&cipher).unwrap());
+        assert_eq!(&plaintext[request.offset as usize..], decrypted.as_slice());
+
+        // chunks cannot be passed off as other chunks of the same clip
+        let mut decryptor = StreamDecryptor::starting_at(1700000000, first_chunk + 1);
+        assert!(decryptor.push(&stream, &cipher).is_err());
+        assert!(DownloadRequest { offset: 5 }.first_chunk().is_none());
+    }
+
+    #[test]
+    fn other_clip() {
+        let cipher: Aes256GcmSiv = Aes256GcmSiv::new(&Aes256GcmSiv::generate_key(
+            rand::rngs::StdRng::from_entropy(),
+        ));
+        let plaintext: Vec<u8> = (0..2 * CHUNK_SIZE + 7).map(|index| index as u8).collect();
+        let stream = encrypt(&plaintext, 1700000000, 0, &cipher);
+
+        // neither its full chunks nor its last one pass for those of another clip
+        let mut decryptor = StreamDecryptor::new(1700000001);
+        assert!(decryptor.push(&stream, &cipher).is_err());
+        let mut last_chunk = stream[..NONCE_PREFIX_LENGTH].to_vec();
+        last_chunk.extend_from_slice(&stream[stream.len() - 7 - TAG_LENGTH..]);
+        for (id, decrypts) in [(1700000000, true), (1700000001, false)] {
+            let mut decryptor = StreamDecryptor::starting_at(id, 2);
+            decryptor.push(&last_chunk, &cipher).unwrap();
+            assert_eq!(decryptor.finish(&cipher).is_ok(), decrypts);
+        }
+    }
+}
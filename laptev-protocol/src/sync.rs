@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+
+/// query parameters of `endpoint::SYNCHRONIZE`
+///
+/// without cursors the most recent entries are sent, `before` and `after` take the `older` and `newer`
+/// cursors of a previously received page, pages stay consistent however many events land in between
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct SyncRequest {
+    // the maximum amount of entries to send back
+    #[serde(default = "SyncRequest::default_size")]
+    pub size: usize,
+    // only entries strictly older than this timestamp, the most recent ones first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<u64>,
+    // only entries strictly newer than this timestamp, the ones right after it first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<u64>,
+}
+
+impl SyncRequest {
+    fn default_size() -> usize {
+        25
+    }
+    /// the page of entries older than the given cursor
+    pub fn older(size: usize, cursor: u64) -> Self {
+        Self {
+            size,
+            before: Some(cursor),
+            after: None,
+        }
+    }
+    /// the page of entries newer than the given cursor
+    pub fn newer(size: usize, cursor: u64) -> Self {
+        Self {
+            size,
+            before: None,
+            after: Some(cursor),
+        }
+    }
+    /// whether the entries right after `after` are wanted rather than the most recent ones
+    pub fn from_oldest(&self) -> bool {
+        self.after.is_some() && self.before.is_none()
+    }
+}
+
+impl Default for SyncRequest {
+    fn default() -> Self {
+        Self {
+            size: Self::default_size(),
+            before: None,
+            after: None,
+        }
+    }
+}
+
+/// a single motion event, identified by the unix timestamp at which it was captured
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SyncEntry {
+    pub timestamp: u64,
+    pub thumbnail: Vec<u8>,
+}
+
+/// the decrypted body of a `endpoint::SYNCHRONIZE` response
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SyncResponse {
+    // most recent entries first
+    pub entries: Vec<SyncEntry>,
+    // the `before` cursor of the page of older entries, None if there are none
+    pub older: Option<u64>,
+    // the `after` cursor of the page of newer entries, None if there are none
+    pub newer: Option<u64>,
+}
@@ -0,0 +1,17 @@
+//! the events deleted by clients, kept in the host's trash for `trash_expiration_time` seconds so that they can be restored
+
+use serde::{Deserialize, Serialize};
+
+/// an event in the trash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TrashedEvent {
+    pub timestamp: u64,
+    pub thumbnail_size: Option<u64>,
+    pub clip_size: Option<u64>,
+    // when the event was deleted, and when it will be purged for good
+    pub deleted_at: u64,
+    pub purge_at: u64,
+}
+
+/// the decrypted body of a `endpoint::TRASH` response, most recently deleted first
+pub type TrashResponse = Vec<TrashedEvent>;